@@ -57,9 +57,15 @@ pub mod vm {
     //! The `vm` module re-exports wasmer-vm types.
 
     pub use wasmer_vm::{
-        MemoryError, MemoryStyle, TableStyle, VMExtern, VMMemory, VMMemoryDefinition, VMTable,
-        VMTableDefinition,
+        set_signal_handler_installation, set_wasm_stack_size, MemoryError, MemoryStyle, TableStyle,
+        VMExtern, VMMemory, VMMemoryDefinition, VMTable, VMTableDefinition,
+        DEFAULT_WASM_STACK_SIZE,
     };
+
+    #[cfg(target_os = "windows")]
+    pub use wasmer_vm::handle_exception;
+    #[cfg(unix)]
+    pub use wasmer_vm::handle_signal;
 }
 
 #[cfg(feature = "wat")]
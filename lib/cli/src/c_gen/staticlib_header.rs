@@ -74,6 +74,7 @@ pub fn generate_header_file(
     symbol_registry: &dyn SymbolRegistry,
     metadata_length: usize,
 ) -> String {
+    let metadata_symbol = symbol_registry.symbol_to_name(Symbol::Metadata);
     let mut c_statements = vec![
         CStatement::LiteralConstant {
             value: "#include \"wasmer.h\"\n#include <stdlib.h>\n#include <string.h>\n\n"
@@ -92,7 +93,7 @@ pub fn generate_header_file(
             })),
         },
         CStatement::Declaration {
-            name: "WASMER_METADATA".to_string(),
+            name: metadata_symbol.clone(),
             is_extern: true,
             is_const: true,
             ctype: CType::Array {
@@ -286,9 +287,16 @@ pub fn generate_header_file(
         });
     }
 
+    // The helpers refer to the metadata by its unprefixed name.
+    c_statements.push(CStatement::LiteralConstant {
+        value: format!("\n#define WASMER_METADATA {}\n", metadata_symbol),
+    });
     c_statements.push(CStatement::LiteralConstant {
         value: HELPER_FUNCTIONS.to_string(),
     });
+    c_statements.push(CStatement::LiteralConstant {
+        value: "\n#undef WASMER_METADATA\n".to_string(),
+    });
 
     c_statements.push(CStatement::LiteralConstant {
         value: "\n#ifdef __cplusplus\n}\n#endif\n\n".to_string(),
@@ -60,7 +60,8 @@ enum WasmerCLIOptions {
     /// to the location of your Wasmer installation. This will probably be `~/.wasmer`. It
     /// should include a `lib`, `include` and `bin` subdirectories. To create an executable
     /// you will need `libwasmer`, so by setting `WASMER_DIR` the CLI knows where to look for
-    /// header files and libraries.
+    /// header files and libraries. When it is not set, `~/.wasmer`, `/usr/local` and the
+    /// installation of the running `wasmer` are tried.
     ///
     /// Example usage:
     ///
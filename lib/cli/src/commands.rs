@@ -36,6 +36,8 @@ pub enum ObjectFormat {
     Serialized,
     /// Serialize only the module metadata into an object file and emit functions as symbols.
     Symbols,
+    /// Emit the LLVM IR of the module instead of an object file. Requires the LLVM compiler.
+    LlvmIr,
 }
 
 #[cfg(any(feature = "static-artifact-create", feature = "wasmer-artifact-create"))]
@@ -46,7 +48,8 @@ impl std::str::FromStr for ObjectFormat {
         match s {
             "serialized" => Ok(Self::Serialized),
             "symbols" => Ok(Self::Symbols),
-            _ => Err("must be one of two options: `serialized` or `symbols`."),
+            "llvm-ir" => Ok(Self::LlvmIr),
+            _ => Err("must be one of three options: `serialized`, `symbols` or `llvm-ir`."),
         }
     }
 }
@@ -1,9 +1,12 @@
 //! Create a standalone native executable for a given Wasm file.
 
 use super::ObjectFormat;
-use crate::store::CompilerOptions;
+use crate::common::get_cache_dir;
+use crate::store::{CompilerOptions, CompilerType};
+use crate::utils::{parse_envvar, parse_mapdir, parse_symbol_prefix};
 use anyhow::{Context, Result};
 use clap::Parser;
+use rayon::prelude::*;
 use std::env;
 use std::fs;
 use std::fs::File;
@@ -11,8 +14,12 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use wasmer::*;
-use wasmer_object::{emit_serialized, get_object_for_target};
+use wasmer_object::{emit_data, emit_serialized, get_object_for_target, Object};
+use wasmer_types::MetadataHeader;
 
 /// The `prefixer` returns the a String to prefix each of the
 /// functions in the static object generated by the
@@ -20,8 +27,23 @@ use wasmer_object::{emit_serialized, get_object_for_target};
 #[cfg(feature = "static-artifact-create")]
 pub type PrefixerFn = Box<dyn Fn(&[u8]) -> String + Send>;
 
-const WASMER_MAIN_C_SOURCE: &[u8] = include_bytes!("wasmer_create_exe_main.c");
+/// Prints to stderr if the verbosity (the number of `-v` flags) is at least `$level`.
+macro_rules! verbose {
+    ($verbosity:expr, $level:expr, $($arg:tt)*) => {
+        if $verbosity >= $level {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+const WASMER_MAIN_C_SOURCE: &str = include_str!("wasmer_create_exe_main.c");
+const WASMER_LIB_C_SOURCE: &str = include_str!("wasmer_create_lib.c");
 const WASMER_DESERIALIZE_HEADER: &str = include_str!("wasmer_deserialize_module.h");
+const WASMER_DECOMPRESS_HEADER: &str = include_str!("wasmer_decompress_module.h");
+
+/// Name of the module of an executable embedding a single one, which the C main gets as
+/// `WASMER_MODULE_NAME`.
+const SINGLE_MODULE_NAME: &str = "module";
 
 #[derive(Debug, Clone)]
 struct CrossCompile {
@@ -35,6 +57,168 @@ struct CrossCompile {
     zig_binary_path: Option<PathBuf>,
 }
 
+/// How `libwasmer` is linked into the generated executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Linkage {
+    /// Link against the static `libwasmer.a` (`wasmer.lib` on Windows).
+    Static,
+    /// Link against the shared `libwasmer.so`/`libwasmer.dylib` (`wasmer.dll` on Windows).
+    Dynamic,
+}
+
+impl Default for Linkage {
+    fn default() -> Self {
+        Self::Static
+    }
+}
+
+/// The kind of native artifact built by `create-exe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputKind {
+    /// An executable running the module's WASI `_start` function.
+    Executable,
+    /// A shared library exporting functions creating and instantiating the module.
+    SharedLibrary,
+}
+
+impl FromStr for OutputKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "executable" => Ok(Self::Executable),
+            "shared-lib" => Ok(Self::SharedLibrary),
+            _ => Err("must be one of two options: `executable` or `shared-lib`."),
+        }
+    }
+}
+
+/// The kind of program used to link the executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkerKind {
+    /// A C compiler driver such as `cc` or `clang`, which takes compiler-style flags.
+    CompilerDriver,
+    /// A linker such as `ld` or `lld`, invoked directly with raw linker flags.
+    ///
+    /// Unlike a compiler driver, a raw linker doesn't add the C runtime startup objects
+    /// on its own.
+    Linker,
+}
+
+impl Default for LinkerKind {
+    fn default() -> Self {
+        Self::CompilerDriver
+    }
+}
+
+impl LinkerKind {
+    /// Guesses the kind of linker from the name of its executable.
+    fn from_linker_path(linker_path: &Path) -> Self {
+        let name = linker_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let name = name.strip_suffix(".exe").unwrap_or(name);
+        match name {
+            "ld" | "lld" | "ld.lld" | "ld64.lld" | "ld.bfd" | "ld.gold" | "mold" | "lld-link"
+            | "link" => Self::Linker,
+            // Cross linkers, e.g. `aarch64-linux-gnu-ld`.
+            name if name.ends_with("-ld") => Self::Linker,
+            _ => Self::CompilerDriver,
+        }
+    }
+
+    /// The flag linking against the library `name`.
+    fn library_flag(self, name: &str) -> String {
+        if self == Self::Linker && cfg!(windows) {
+            format!("{}.lib", name)
+        } else {
+            format!("-l{}", name)
+        }
+    }
+
+    /// The flag adding `dir` to the directories searched for libraries.
+    fn library_path_flag(self, dir: &Path) -> String {
+        if self == Self::Linker && cfg!(windows) {
+            format!("/LIBPATH:{}", dir.display())
+        } else {
+            format!("-L{}", dir.display())
+        }
+    }
+
+    /// The flags linking against the system libraries libwasmer depends on.
+    fn default_library_flags(self) -> Vec<String> {
+        if cfg!(windows) {
+            // We need userenv, sockets (Ws2_32), advapi32 for some system calls and bcrypt for random numbers.
+            ["userenv", "Ws2_32", "advapi32", "bcrypt"]
+                .iter()
+                .map(|lib| self.library_flag(lib))
+                .collect()
+        } else {
+            // On unix we need dlopen-related symbols, libmath for a few things, and pthreads.
+            let mut flags: Vec<String> = ["dl", "m"]
+                .iter()
+                .map(|lib| self.library_flag(lib))
+                .collect();
+            match self {
+                Self::CompilerDriver => flags.push("-pthread".to_string()),
+                // Without a compiler driver, pthreads and libc have to be asked for explicitly.
+                Self::Linker => {
+                    flags.push(self.library_flag("pthread"));
+                    flags.push(self.library_flag("c"));
+                }
+            }
+            flags
+        }
+    }
+}
+
+/// A serialized module embedded in the executable.
+struct EmbeddedModule {
+    /// Name selecting the module on the command line of the executable.
+    name: String,
+    /// Prefix of the `_LENGTH` and `_DATA` symbols holding the serialized module.
+    symbol: String,
+    /// Wasm file the module was compiled from.
+    source: PathBuf,
+    /// Length in bytes of the serialized module.
+    length: usize,
+}
+
+/// Operating systems and environments executables can be cross-compiled for, as the part of
+/// the target triple following the architecture.
+const CROSS_COMPILE_SYSTEMS: &[&str] = &[
+    "unknown-linux-gnu",
+    "unknown-linux-musl",
+    "apple-darwin",
+    "pc-windows-gnu",
+];
+
+/// The compiler, target and object format an executable is built with, as selected by the
+/// command line.
+struct BuildPlan {
+    store: Store,
+    compiler_type: CompilerType,
+    target: Target,
+    object_format: ObjectFormat,
+    features: Features,
+}
+
+impl BuildPlan {
+    /// Prints the plan at `-v`.
+    fn print(&self, verbose: u8) {
+        verbose!(verbose, 1, "Compiler: {}", self.compiler_type.to_string());
+        verbose!(verbose, 1, "Target: {}", self.target.triple());
+        verbose!(verbose, 1, "Format: {:?}", self.object_format);
+        verbose!(
+            verbose,
+            1,
+            "Wasm features: {}",
+            enabled_features(&self.features).join(", ")
+        );
+    }
+}
+
 struct CrossCompileSetup {
     target: Triple,
     zig_binary_path: PathBuf,
@@ -45,13 +229,49 @@ struct CrossCompileSetup {
 #[derive(Debug, Parser)]
 /// The options for the `wasmer create-exe` subcommand
 pub struct CreateExe {
-    /// Input file
-    #[clap(name = "FILE", parse(from_os_str))]
-    path: PathBuf,
+    /// Input file(s)
+    ///
+    /// When several Wasm files are given, they are all embedded in the executable, and the
+    /// first argument of the executable selects which one to run by its file stem. This
+    /// requires the `serialized` object format. Use `-` to read the input from stdin.
+    #[clap(
+        name = "FILE",
+        parse(from_os_str),
+        required_unless_present = "list-targets"
+    )]
+    path: Vec<PathBuf>,
+
+    /// Print the target triples the selected compiler can build executables for, then exit
+    #[clap(long = "list-targets")]
+    list_targets: bool,
 
     /// Output file
-    #[clap(name = "OUTPUT PATH", short = 'o', parse(from_os_str))]
-    output: PathBuf,
+    ///
+    /// If it is a directory, the executable is written in it, named after the first input.
+    /// Windows executables get an `.exe` extension if the file name has none.
+    #[clap(
+        name = "OUTPUT PATH",
+        short = 'o',
+        parse(from_os_str),
+        required_unless_present = "list-targets"
+    )]
+    output: Option<PathBuf>,
+
+    /// Don't add an `.exe` extension to Windows executables whose name has no extension
+    #[clap(long = "no-exe-extension")]
+    no_exe_extension: bool,
+
+    /// The kind of artifact to build: `executable` or `shared-lib`
+    ///
+    /// A shared library (`.so`, `.dylib` or `.dll`, added if the output has no extension)
+    /// exports `wasmer_module_new` and `wasmer_module_instantiate`, which create and
+    /// instantiate the embedded module in a store of the program loading it.
+    #[clap(
+        long = "output-kind",
+        default_value = "executable",
+        conflicts_with = "run"
+    )]
+    output_kind: OutputKind,
 
     /// Compilation Target triple
     ///
@@ -92,18 +312,316 @@ pub struct CreateExe {
     /// Header file for object input
     ///
     /// If given, the input `PATH` is assumed to be an object created with `wasmer create-obj` and
-    /// this is its accompanying header file.
+    /// this is its accompanying header file. The header must define `wasmer_object_module_new`
+    /// and declare `extern` the symbols it needs from the object, which is checked before
+    /// linking.
     #[clap(name = "HEADER", long = "header", verbatim_doc_comment)]
     header: Option<PathBuf>,
 
+    /// Prefix for the names of the symbols of the compiled module
+    ///
+    /// Use this to link the intermediate object files of several executables into the same
+    /// program without their symbols colliding. With the `symbols` format, the prefix
+    /// defaults to a hash of the Wasm module.
+    #[clap(
+        long = "symbol-prefix",
+        conflicts_with = "HEADER",
+        parse(try_from_str = parse_symbol_prefix)
+    )]
+    symbol_prefix: Option<String>,
+
     #[clap(short = 'm')]
     cpu_features: Vec<CpuFeature>,
 
+    /// Write the disassembly of the compiled module to this file
+    ///
+    /// The object is disassembled with `objdump`, or the program in the `OBJDUMP`
+    /// environment variable, and the label of each function is followed by its name in the
+    /// Wasm module. Requires `--object-format symbols`.
+    #[clap(long = "emit-asm", parse(from_os_str), conflicts_with = "HEADER")]
+    emit_asm: Option<PathBuf>,
+
+    /// Write a Makefile dependency file listing what the executable was built from
+    ///
+    /// The Wasm inputs, the header given with `--header`, the Wasmer headers and libwasmer are
+    /// listed as prerequisites of the executable, so that build systems like Make or Ninja
+    /// know when to run `create-exe` again. Input read from stdin isn't listed.
+    #[clap(long = "depfile", parse(from_os_str))]
+    depfile: Option<PathBuf>,
+
+    /// Write the object file holding the compiled Wasm module to the output path instead of
+    /// building an executable, with its header next to it (`foo.o` and `foo.h`)
+    ///
+    /// The C glue code isn't compiled and nothing is linked: the object and the header are the
+    /// ones `create-exe` would link into the executable, ready to be linked into another
+    /// program along with libwasmer (and `zstd` with `--compress-modules`).
+    #[clap(
+        long = "emit-object",
+        conflicts_with_all = &["HEADER", "run", "check", "dry-run", "strip", "depfile", "map"]
+    )]
+    emit_object: bool,
+
+    /// Name of the CPU to compile for, such as `haswell`, `skylake-avx512` or `x86-64-v3`.
+    ///
+    /// The features of that CPU are enabled, along with the ones given with `-m`. The
+    /// target defaults to the host when `--target` isn't given.
+    #[clap(long = "cpu")]
+    cpu: Option<String>,
+
     /// Additional libraries to link against.
     /// This is useful for fixing linker errors that may occur on some systems.
+    ///
+    /// A path to an existing file, such as `-l vendor/libfoo.a`, is linked as is instead of
+    /// being looked up by name.
     #[clap(short = 'l')]
     libraries: Vec<String>,
 
+    /// Additional directories to search for the libraries given with `-l`.
+    #[clap(short = 'L', parse(from_os_str))]
+    library_paths: Vec<PathBuf>,
+
+    /// Don't link against the system libraries libwasmer depends on by default
+    ///
+    /// These are `dl`, `m` and pthreads on Unix, and `userenv`, `Ws2_32`, `advapi32` and
+    /// `bcrypt` on Windows. Give the ones the target needs with `-l` instead, such as for
+    /// musl or custom sysroots.
+    #[clap(long = "no-default-libs")]
+    no_default_libs: bool,
+
+    /// Keep the unused functions and data of the C glue code and libwasmer in the executable
+    ///
+    /// By default, the C glue code is compiled with `-ffunction-sections -fdata-sections` and
+    /// the linker drops the unused sections (`--gc-sections` on ELF, `-dead_strip` on
+    /// Mach-O), which makes the executable smaller.
+    #[clap(long = "no-gc-sections")]
+    no_gc_sections: bool,
+
+    /// Fail the build if compiling the generated C glue code gives any warning
+    ///
+    /// The glue code is compiled with `-Wall -Werror`. This is meant for checking that the
+    /// generated code is clean; warnings differ between C compilers and their versions.
+    #[clap(long = "strict-c", alias = "werror")]
+    strict_c: bool,
+
+    /// Build the executable reproducibly: the same inputs give a byte-identical executable
+    ///
+    /// The path of the temporary directory the C glue code is compiled in is left out of the
+    /// objects, and the linker is asked not to embed build IDs or timestamps. This is also
+    /// enabled when the `SOURCE_DATE_EPOCH` environment variable is set.
+    #[clap(long = "reproducible")]
+    reproducible: bool,
+
+    /// Write the linker map of the executable to this file, for size analysis tools like
+    /// `bloaty`
+    ///
+    /// Not supported when cross-compiling with `--target`.
+    #[clap(long = "map", parse(from_os_str))]
+    map: Option<PathBuf>,
+
+    /// Optimization level used to compile and link the C glue code: `0`, `1`, `2`, `3`, `s`
+    /// or `z`
+    #[clap(
+        short = 'O',
+        long = "optimization-level",
+        default_value = "2",
+        possible_values = &["0", "1", "2", "3", "s", "z"]
+    )]
+    optimization_level: String,
+
+    /// Link `libwasmer` statically into the executable (default)
+    #[clap(long = "static", conflicts_with = "link-dynamic")]
+    link_static: bool,
+
+    /// Link `libwasmer` dynamically against the shared library
+    ///
+    /// The resulting executable will need the shared `libwasmer` at runtime.
+    #[clap(long = "dynamic", conflicts_with = "link-static")]
+    link_dynamic: bool,
+
+    /// C compiler used to compile the generated C code and link the executable
+    ///
+    /// Defaults to the `WASMER_CC` or `CC` environment variable if set, otherwise to `cc`
+    /// (`clang++` on Windows). Flags from the `CFLAGS` environment variable are added when
+    /// compiling.
+    #[clap(long = "cc", parse(from_os_str))]
+    cc: Option<PathBuf>,
+
+    /// Linker used to link the executable, e.g. `lld` or a full path to a linker
+    ///
+    /// Defaults to the `LD` environment variable if set, otherwise to linking through the C
+    /// compiler. Linkers such as `ld` or `lld` are invoked directly with raw linker flags.
+    /// Flags from the `LDFLAGS` environment variable are added when linking.
+    #[clap(long = "linker", parse(from_os_str))]
+    linker: Option<PathBuf>,
+
+    /// Pass an argument to the C compiler compiling the C glue code, after the default flags;
+    /// repeat for several arguments.
+    #[clap(long = "cc-arg", allow_hyphen_values = true)]
+    cc_args: Vec<String>,
+
+    /// Pass an argument to the linker, after the default flags; repeat for several arguments.
+    ///
+    /// For example `--linker-arg -Wl,--gc-sections` when linking through the C compiler.
+    #[clap(long = "linker-arg", allow_hyphen_values = true)]
+    linker_args: Vec<String>,
+
+    /// Keep the intermediate files (objects, headers, C code) in the given directory
+    ///
+    /// By default, they are written to a temporary directory which is removed afterwards.
+    #[clap(long = "keep-intermediate", parse(from_os_str))]
+    keep_intermediate: Option<PathBuf>,
+
+    /// Directory of previously serialized modules to reuse with `--object-format serialized`.
+    ///
+    /// Entries are keyed by the module name and a hash of the Wasm bytes, the target triple,
+    /// the compiler, the enabled Wasm features and the Wasmer version, so stale entries are
+    /// ignored. Modules missing from the directory are compiled and stored there for the next
+    /// build. Defaults to a directory in the Wasmer cache.
+    #[clap(long = "precompiled-atom", parse(from_os_str))]
+    precompiled_atom: Option<PathBuf>,
+
+    /// Always compile the Wasm modules instead of reusing serialized modules from the cache
+    #[clap(long = "no-cache", conflicts_with = "precompiled-atom")]
+    no_cache: bool,
+
+    /// Directory of the Wasmer cache, where serialized modules are reused from.
+    ///
+    /// Defaults to the `WASMER_CACHE_DIR` environment variable, then to a `wasmer` directory
+    /// in the platform cache directory (such as `~/.cache` on Linux), then to one in the system
+    /// temporary directory. Caching is disabled with a warning if it isn't writable.
+    #[clap(
+        long = "cache-dir",
+        parse(from_os_str),
+        conflicts_with_all = &["precompiled-atom", "no-cache"]
+    )]
+    cache_dir: Option<PathBuf>,
+
+    /// Write a JSON manifest describing the modules embedded in the executable.
+    ///
+    /// Lists the symbol, serialized length and source file of each module along with the
+    /// target triple and compiler. Requires `--object-format serialized`.
+    #[clap(long = "emit-manifest", parse(from_os_str))]
+    emit_manifest: Option<PathBuf>,
+
+    /// Compress the embedded modules with zstd, and decompress them when the executable starts.
+    ///
+    /// This makes the executable smaller at the cost of a slower startup. The executable is
+    /// linked against `libzstd`, which must be installed. Requires `--object-format serialized`.
+    #[clap(long = "compress-modules", conflicts_with = "HEADER")]
+    compress_modules: bool,
+
+    /// Build the C glue code with debug information (`-g`).
+    ///
+    /// The intermediate files, including the generated `wasmer_main.c`, are kept in
+    /// `<OUTPUT PATH>.debug` (or the `--keep-intermediate` directory) so that debuggers can
+    /// find the sources.
+    #[clap(long = "debug", conflicts_with = "strip")]
+    debug: bool,
+
+    /// Strip symbols from the produced executable.
+    ///
+    /// The strip tool can be set with the `WASMER_STRIP` environment variable; otherwise
+    /// `<target>-strip` is tried first when cross-compiling, then `llvm-strip` and `strip`.
+    #[clap(long = "strip")]
+    strip: bool,
+
+    /// Don't build a position-independent executable.
+    ///
+    /// On Linux, the C glue code is compiled with `-fPIC` and the executable is linked with
+    /// `-pie` by default; this links it with `-no-pie` instead.
+    #[clap(long = "no-pie")]
+    no_pie: bool,
+
+    /// Map a host directory to a directory of the WASI module by default.
+    ///
+    /// More directories can be mapped when running the executable with its own `--dir` and
+    /// `--mapdir` options.
+    #[clap(
+        long = "mapdir",
+        name = "GUEST_DIR:HOST_DIR",
+        parse(try_from_str = parse_mapdir),
+    )]
+    mapped_dirs: Vec<(String, PathBuf)>,
+
+    /// Set an environment variable of the WASI module by default.
+    ///
+    /// The executable's own `--env KEY=VALUE` option overrides it.
+    #[clap(
+        long = "env",
+        name = "KEY=VALUE",
+        parse(try_from_str = parse_envvar),
+    )]
+    env_vars: Vec<(String, String)>,
+
+    /// Pass an argument to the WASI module by default; repeat for several arguments.
+    ///
+    /// These arguments are only used when the executable is run without guest arguments.
+    #[clap(long = "arg", name = "ARG", allow_hyphen_values = true)]
+    wasi_args: Vec<String>,
+
+    /// Only let the WASI module use these capabilities, separated by commas (`--allow fs,clock`)
+    ///
+    /// The WASI functions of the other capabilities are replaced in the executable by functions
+    /// returning `ENOSYS`. `fs` covers all the `fd_` functions, including writing to stdout;
+    /// `proc_exit`, `proc_raise` and `sched_yield` are always allowed, and `none` allows nothing
+    /// else. By default, the module can use all of WASI.
+    #[clap(
+        long = "allow",
+        value_name = "CAPABILITIES",
+        use_value_delimiter = true,
+        possible_values = &["args", "env", "clock", "random", "fs", "poll", "sock", "none"]
+    )]
+    allowed_wasi_capabilities: Option<Vec<String>>,
+
+    /// Print the commands compiling the C glue code and linking the executable instead of
+    /// running them.
+    ///
+    /// The Wasm module is still compiled; use `--keep-intermediate` to keep the files the
+    /// printed commands refer to.
+    #[clap(long = "dry-run", conflicts_with = "run")]
+    dry_run: bool,
+
+    /// Check that the executable can be built, without keeping it.
+    ///
+    /// Unlike `--dry-run`, the C glue code is compiled and the executable is linked, but into a
+    /// temporary directory that is deleted afterwards. The command fails if the build fails.
+    #[clap(long = "check", conflicts_with_all = &["dry-run", "run", "depfile"])]
+    check: bool,
+
+    /// Print how long each phase of the build took: compiling the Wasm modules, writing the
+    /// object file, compiling the C glue code and linking.
+    #[clap(long = "timings")]
+    timings: bool,
+
+    /// Durations of the phases of the build, in the order they finished.
+    #[clap(skip)]
+    phase_durations: Mutex<Vec<(String, Duration)>>,
+
+    /// Sizes in bytes of the parts of the executable, in the order they were built.
+    #[clap(skip)]
+    part_sizes: Mutex<Vec<(String, u64)>>,
+
+    /// Run the executable once it is built, passing it the arguments given after `--`.
+    ///
+    /// `wasmer create-exe` then exits with the exit code of the executable.
+    #[clap(long = "run")]
+    run: bool,
+
+    /// Arguments passed to the executable by `--run`.
+    #[clap(value_name = "ARGS", last = true, requires = "run")]
+    runtime_args: Vec<String>,
+
+    /// Compile at most this many Wasm modules at the same time
+    ///
+    /// Compiling large modules, especially with LLVM, takes a lot of memory. `0`, the default,
+    /// compiles as many modules at the same time as there are CPUs.
+    #[clap(short = 'j', long = "jobs", default_value = "0")]
+    jobs: usize,
+
+    /// Print more details about the build; repeat (`-vv`) for even more
+    #[clap(short = 'v', long = "verbose", parse(from_occurrences))]
+    verbose: u8,
+
     #[clap(flatten)]
     compiler: CompilerOptions,
 }
@@ -111,10 +629,112 @@ pub struct CreateExe {
 impl CreateExe {
     /// Runs logic for the `compile` subcommand
     pub fn execute(&self) -> Result<()> {
-        let object_format = self.object_format.unwrap_or(ObjectFormat::Symbols);
-        let working_dir = tempfile::tempdir()?;
+        let build_start = Instant::now();
+        if self.list_targets {
+            return self.print_targets();
+        }
+        self.preflight_check()?;
+
+        let plan = self.plan()?;
+        let object_format = plan.object_format;
+        if let ObjectFormat::LlvmIr = object_format {
+            bail!("An executable can't be built from LLVM IR; use `wasmer create-obj --object-format llvm-ir` to emit it.");
+        }
+        let linkage = self.linkage();
+        let (c_compiler, c_compiler_args) = self.c_compiler()?;
+
+        if self.path.len() > 1 {
+            if self.header.is_some() {
+                bail!("Only one object file can be given along with `--header`.");
+            }
+            if !matches!(object_format, ObjectFormat::Serialized) {
+                bail!("Building an executable from several Wasm files requires `--object-format serialized`.");
+            }
+        }
+        if matches!(object_format, ObjectFormat::Serialized) && self.target_triple.is_some() {
+            bail!("Cross-compilation with serialized object format is not implemented.");
+        }
+        if self.output_kind == OutputKind::SharedLibrary && self.target_triple.is_some() {
+            bail!(
+                "`--output-kind shared-lib` cannot be used when cross-compiling with `--target`."
+            );
+        }
+        if self.map.is_some() && self.target_triple.is_some() {
+            bail!("`--map` cannot be used when cross-compiling with `--target`.");
+        }
+        if self.run && self.target_triple.is_some() {
+            bail!("`--run` cannot run an executable cross-compiled with `--target`.");
+        }
         let starting_cd = env::current_dir()?;
-        let output_path = starting_cd.join(&self.output);
+        let temp_dir = tempfile::tempdir()?;
+        let wasm_module_paths = self.input_paths(&starting_cd, temp_dir.path())?;
+        if self.header.is_none() {
+            for path in wasm_module_paths.iter() {
+                check_wasm_input(path)?;
+            }
+        }
+        if self.emit_asm.is_some() && !matches!(object_format, ObjectFormat::Symbols) {
+            bail!("`--emit-asm` requires `--object-format symbols`.");
+        }
+        if !matches!(object_format, ObjectFormat::Serialized) {
+            if self.precompiled_atom.is_some() {
+                bail!("`--precompiled-atom` requires `--object-format serialized`.");
+            }
+            if self.emit_manifest.is_some() {
+                bail!("`--emit-manifest` requires `--object-format serialized`.");
+            }
+            if self.compress_modules {
+                bail!("`--compress-modules` requires `--object-format serialized`.");
+            }
+        }
+        let check_dir = if self.check {
+            Some(tempfile::tempdir()?)
+        } else {
+            None
+        };
+        let output = match check_dir.as_ref() {
+            Some(dir) => dir.path().join(
+                self.output()
+                    .file_name()
+                    .unwrap_or_else(|| std::ffi::OsStr::new("wasm")),
+            ),
+            None => self.output(),
+        };
+        let output_path = starting_cd.join(&output);
+        // Only serialized modules are compiled from Wasm files and reused from the cache.
+        let precompiled_dir = match self.precompiled_atom.as_ref() {
+            Some(dir) => Some(starting_cd.join(dir)),
+            None if self.no_cache
+                || self.header.is_some()
+                || !matches!(object_format, ObjectFormat::Serialized) =>
+            {
+                None
+            }
+            None => {
+                let cache_dir = match self.cache_dir.as_ref() {
+                    Some(dir) => starting_cd.join(dir).join(crate::VERSION),
+                    None => default_cache_dir(),
+                };
+                writable_cache_dir(cache_dir.join("create-exe"))
+            }
+        };
+        let kept_intermediate_dir = match self.keep_intermediate.as_ref() {
+            Some(dir) => Some(starting_cd.join(dir)),
+            None if self.debug => {
+                let mut dir = output_path.clone().into_os_string();
+                dir.push(".debug");
+                Some(PathBuf::from(dir))
+            }
+            None => None,
+        };
+        let working_dir = match kept_intermediate_dir.as_ref() {
+            Some(dir) => {
+                fs::create_dir_all(&dir)
+                    .with_context(|| format!("Could not create directory `{}`", dir.display()))?;
+                dir.clone()
+            }
+            None => temp_dir.path().to_path_buf(),
+        };
 
         /* Making library_path, tarball zig_binary_path flags require that target_triple flag
          * is set cannot be encoded with structopt, so we have to perform cli flag validation
@@ -137,24 +757,6 @@ impl CreateExe {
             None
         };
 
-        let target = self
-            .target_triple
-            .as_ref()
-            .map(|target_triple| {
-                let mut features = self
-                    .cpu_features
-                    .clone()
-                    .into_iter()
-                    .fold(CpuFeature::set(), |a, b| a | b);
-                // Cranelift requires SSE2, so we have this "hack" for now to facilitate
-                // usage
-                if target_triple.architecture == Architecture::X86_64 {
-                    features |= CpuFeature::SSE2;
-                }
-                Target::new(target_triple.clone(), features)
-            })
-            .unwrap_or_default();
-
         env::set_current_dir(&working_dir)?;
 
         let cross_compilation: Option<CrossCompileSetup> = if let Some(mut cross_subc) =
@@ -169,9 +771,9 @@ impl CreateExe {
                     None
                 }
             }) {
-            if let ObjectFormat::Serialized = object_format {
+            if linkage == Linkage::Dynamic {
                 return Err(anyhow!(
-                    "Cross-compilation with serialized object format is not implemented."
+                    "Cross-compilation only supports linking libwasmer statically."
                 ));
             }
 
@@ -257,26 +859,29 @@ impl CreateExe {
                 target,
                 zig_binary_path,
                 library,
-                working_dir: working_dir.path().to_path_buf(),
+                working_dir: working_dir.clone(),
             })
         } else {
             None
         };
 
-        let (store, compiler_type) = self.compiler.get_store_for_target(target.clone())?;
-
-        println!("Compiler: {}", compiler_type.to_string());
-        println!("Target: {}", target.triple());
-        println!("Format: {:?}", object_format);
+        plan.print(self.verbose);
+        let BuildPlan {
+            store,
+            compiler_type,
+            target,
+            features,
+            ..
+        } = plan;
 
         #[cfg(not(windows))]
-        let wasm_object_path = working_dir.path().join("wasm.o");
+        let wasm_object_path = working_dir.join("wasm.o");
         #[cfg(windows)]
-        let wasm_object_path = working_dir.path().join("wasm.obj");
+        let wasm_object_path = working_dir.join("wasm.obj");
 
-        let wasm_module_path = starting_cd.join(&self.path);
+        let wasm_module_path = wasm_module_paths[0].clone();
 
-        let static_defs_header_path: PathBuf = working_dir.path().join("static_defs.h");
+        let static_defs_header_path: PathBuf = working_dir.join("static_defs.h");
 
         if let Some(header_path) = self.header.as_ref() {
             /* In this case, since a header file is given, the input file is expected to be an
@@ -285,6 +890,8 @@ impl CreateExe {
             std::fs::copy(&header_path, &static_defs_header_path)
                 .context("Could not access given header file")?;
             let object_file_path = wasm_module_path;
+            validate_header_matches_object(&header_path, &object_file_path)?;
+            self.record_file_size("Wasm object", &object_file_path);
             if let Some(setup) = cross_compilation.as_ref() {
                 self.compile_zig(
                     output_path,
@@ -296,64 +903,231 @@ impl CreateExe {
                 self.link(
                     static_defs_header_path,
                     LinkCode {
-                        object_paths: vec![object_file_path, "main_obj.obj".into()],
-                        output_path,
-                        working_dir: working_dir.path().to_path_buf(),
-                        ..Default::default()
+                        object_paths: vec![object_file_path],
+                        working_dir: working_dir.clone(),
+                        ..self.link_code(&starting_cd, output_path)?
                     },
                 )?;
             }
         } else {
             match object_format {
+                ObjectFormat::LlvmIr => unreachable!("rejected at the start of `execute`"),
                 ObjectFormat::Serialized => {
-                    let module = Module::from_file(&store, &wasm_module_path)
-                        .context("failed to compile Wasm")?;
-                    let bytes = module.serialize()?;
-                    let mut obj = get_object_for_target(target.triple())?;
-                    emit_serialized(&mut obj, &bytes, target.triple())?;
+                    let mut obj = object_for_target(target.triple())?;
+                    // Inputs with identical contents are compiled and embedded only once,
+                    // and share the same symbol.
+                    let mut unique_paths: Vec<&PathBuf> = vec![];
+                    let mut unique_indices = Vec::with_capacity(wasm_module_paths.len());
+                    let mut seen = std::collections::HashMap::new();
+                    for wasm_module_path in wasm_module_paths.iter() {
+                        let bytes = fs::read(wasm_module_path).with_context(|| {
+                            format!("Could not read `{}`", wasm_module_path.display())
+                        })?;
+                        let index = *seen.entry(blake3::hash(&bytes)).or_insert_with(|| {
+                            unique_paths.push(wasm_module_path);
+                            unique_paths.len() - 1
+                        });
+                        unique_indices.push(index);
+                    }
+                    // Modules are compiled concurrently, each thread with its own store, and
+                    // emitted in the order they were given for a deterministic object file.
+                    let pool = rayon::ThreadPoolBuilder::new()
+                        .num_threads(self.jobs)
+                        .build()
+                        .context("Could not start the threads compiling the Wasm modules")?;
+                    let serialized_modules = pool.install(|| {
+                        unique_paths
+                            .par_iter()
+                            .map(|wasm_module_path| -> anyhow::Result<Vec<u8>> {
+                                let start = Instant::now();
+                                let (store, compiler_type) =
+                                    self.compiler.get_store_for_target(target.clone())?;
+                                let bytes = self.serialize_module(
+                                    &store,
+                                    &compiler_type.to_string(),
+                                    wasm_module_path,
+                                    &target,
+                                    precompiled_dir.as_deref(),
+                                )?;
+                                self.record_phase(
+                                    format!(
+                                        "compile `{}`",
+                                        wasm_module_path
+                                            .file_name()
+                                            .unwrap_or_default()
+                                            .to_string_lossy()
+                                    ),
+                                    start,
+                                );
+                                Ok(bytes)
+                            })
+                            .collect::<anyhow::Result<Vec<_>>>()
+                    })?;
+                    let start = Instant::now();
+                    let symbol_prefix = self.symbol_prefix.as_deref().unwrap_or_default();
+                    let symbols = (0..serialized_modules.len())
+                        .map(|index| {
+                            if wasm_module_paths.len() == 1 {
+                                format!("{}WASMER_MODULE", symbol_prefix)
+                            } else {
+                                format!("{}WASMER_MODULE_{}", symbol_prefix, index)
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    for ((bytes, symbol), wasm_module_path) in serialized_modules
+                        .iter()
+                        .zip(symbols.iter())
+                        .zip(unique_paths.iter())
+                    {
+                        if !self.compress_modules {
+                            emit_serialized(
+                                &mut obj,
+                                bytes,
+                                target.triple(),
+                                symbol,
+                                MetadataHeader::ALIGN as u64,
+                            )?;
+                            continue;
+                        }
+                        let compressed =
+                            zstd::bulk::compress(bytes, zstd::DEFAULT_COMPRESSION_LEVEL)
+                                .with_context(|| {
+                                    format!(
+                                        "Could not compress the module compiled from `{}`",
+                                        wasm_module_path.display()
+                                    )
+                                })?;
+                        verbose!(
+                            self.verbose,
+                            1,
+                            "Compressed `{}`: {} bytes -> {} bytes ({:.1}%)",
+                            wasm_module_path.display(),
+                            bytes.len(),
+                            compressed.len(),
+                            compressed.len() as f64 * 100.0 / bytes.len() as f64
+                        );
+                        emit_serialized(
+                            &mut obj,
+                            &compressed,
+                            target.triple(),
+                            symbol,
+                            MetadataHeader::ALIGN as u64,
+                        )?;
+                        emit_data(
+                            &mut obj,
+                            format!("{}_ORIGINAL_LENGTH", symbol).as_bytes(),
+                            &bytes.len().to_le_bytes(),
+                            std::mem::size_of::<usize>() as u64,
+                        )?;
+                    }
+                    let mut embedded_modules = Vec::with_capacity(wasm_module_paths.len());
+                    for (wasm_module_path, &index) in
+                        wasm_module_paths.iter().zip(unique_indices.iter())
+                    {
+                        let name = if wasm_module_paths.len() == 1 {
+                            SINGLE_MODULE_NAME.to_string()
+                        } else {
+                            module_name_from_path(wasm_module_path)?
+                        };
+                        embedded_modules.push(EmbeddedModule {
+                            name,
+                            symbol: symbols[index].clone(),
+                            source: wasm_module_path.clone(),
+                            length: serialized_modules[index].len(),
+                        });
+                    }
+                    if let Some(manifest_path) = self.emit_manifest.as_ref() {
+                        write_manifest(
+                            &starting_cd.join(manifest_path),
+                            &embedded_modules,
+                            target.triple(),
+                            &compiler_type.to_string(),
+                            &features,
+                        )?;
+                    }
                     let mut writer = BufWriter::new(File::create(&wasm_object_path)?);
                     obj.write_stream(&mut writer)
                         .map_err(|err| anyhow::anyhow!(err.to_string()))?;
                     writer.flush()?;
                     drop(writer);
+                    for module in embedded_modules.iter() {
+                        self.record_size(
+                            format!(
+                                "serialized module `{}`",
+                                module
+                                    .source
+                                    .file_name()
+                                    .unwrap_or_default()
+                                    .to_string_lossy()
+                            ),
+                            module.length as u64,
+                        );
+                    }
+                    self.record_file_size("Wasm object", &wasm_object_path);
                     // Write down header file that includes deserialize function
                     {
+                        let header_file_src = if embedded_modules.len() == 1 {
+                            serialized_header(self.symbol_prefix.as_deref(), self.compress_modules)
+                        } else {
+                            generate_dispatch_header(&embedded_modules, self.compress_modules)?
+                        };
                         let mut writer = BufWriter::new(File::create(&static_defs_header_path)?);
-                        writer.write_all(WASMER_DESERIALIZE_HEADER.as_bytes())?;
+                        writer.write_all(header_file_src.as_bytes())?;
                         writer.flush()?;
                     }
+                    self.record_phase("write object", start);
+                    if self.emit_object {
+                        return self.write_emitted_object(
+                            &wasm_object_path,
+                            &static_defs_header_path,
+                            &output_path,
+                        );
+                    }
 
                     // write C src to disk
-                    let c_src_path: PathBuf = working_dir.path().join("wasmer_main.c");
+                    let c_src_path: PathBuf = working_dir.join("wasmer_main.c");
                     #[cfg(not(windows))]
-                    let c_src_obj: PathBuf = working_dir.path().join("wasmer_main.o");
+                    let c_src_obj: PathBuf = working_dir.join("wasmer_main.o");
                     #[cfg(windows)]
-                    let c_src_obj: PathBuf = working_dir.path().join("wasmer_main.obj");
+                    let c_src_obj: PathBuf = working_dir.join("wasmer_main.obj");
 
                     {
                         let mut c_src_file = fs::OpenOptions::new()
-                            .create_new(true)
+                            .create(true)
+                            .truncate(true)
                             .write(true)
                             .open(&c_src_path)
                             .context("Failed to open C source code file")?;
-                        c_src_file.write_all(WASMER_MAIN_C_SOURCE)?;
+                        c_src_file.write_all(self.glue_c_source().as_bytes())?;
                     }
+                    let start = Instant::now();
                     run_c_compile(
+                        &c_compiler,
+                        &c_compiler_args,
                         &c_src_path,
                         &c_src_obj,
                         static_defs_header_path,
-                        self.target_triple.clone(),
+                        &self.optimization_flag(),
+                        self.debug,
+                        self.pie(),
+                        !self.no_gc_sections,
+                        self.strict_c,
+                        &self.cc_args_in(&working_dir),
+                        self.dry_run,
                     )
                     .context("Failed to compile C source code")?;
-                    LinkCode {
+                    self.record_phase("compile C glue code", start);
+                    self.record_file_size("C glue object", &c_src_obj);
+                    let start = Instant::now();
+                    let mut link_code = LinkCode {
                         object_paths: vec![c_src_obj, wasm_object_path],
-                        output_path,
-                        additional_libraries: self.libraries.clone(),
-                        target: self.target_triple.clone(),
-                        ..Default::default()
+                        ..self.link_code(&starting_cd, output_path)?
+                    };
+                    if self.compress_modules {
+                        link_code.additional_libraries.push("zstd".to_string());
                     }
-                    .run()
-                    .context("Failed to link objects together")?;
+                    link_code.run().context("Failed to link objects together")?;
+                    self.record_phase("link", start);
                 }
                 #[cfg(not(feature = "static-artifact-create"))]
                 ObjectFormat::Symbols => {
@@ -361,34 +1135,64 @@ impl CreateExe {
                 }
                 #[cfg(feature = "static-artifact-create")]
                 ObjectFormat::Symbols => {
-                    let engine = store.engine();
-                    let engine_inner = engine.inner();
-                    let compiler = engine_inner.compiler()?;
-                    let features = engine_inner.features();
-                    let tunables = store.tunables();
-                    let data: Vec<u8> = fs::read(wasm_module_path)?;
-                    let prefixer: Option<PrefixerFn> = None;
-                    let (module_info, obj, metadata_length, symbol_registry) =
-                        Artifact::generate_object(
-                            compiler, &data, prefixer, &target, tunables, features,
-                        )?;
-
-                    let header_file_src = crate::c_gen::staticlib_header::generate_header_file(
-                        &module_info,
-                        &*symbol_registry,
-                        metadata_length,
+                    let data: Vec<u8> = fs::read(&wasm_module_path)?;
+                    #[cfg(feature = "wat")]
+                    let data = if is_wasm(&data) {
+                        data
+                    } else {
+                        wat2wasm(&data)?.into_owned()
+                    };
+                    // Build the same object and header pair as `create-obj`, then link it
+                    // like an object given with `--header`.
+                    #[cfg(not(windows))]
+                    let object_file_path = working_dir.join("functions.o");
+                    #[cfg(windows)]
+                    let object_file_path = working_dir.join("functions.obj");
+                    let start = Instant::now();
+                    let symbols_object = super::create_obj::generate_symbols_object(
+                        &store,
+                        &target,
+                        &data,
+                        self.symbol_prefix.as_deref(),
+                    )?;
+                    self.record_phase(
+                        format!(
+                            "compile `{}`",
+                            wasm_module_path
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                        ),
+                        start,
                     );
-                    // Write object file with functions
-                    let object_file_path: std::path::PathBuf =
-                        working_dir.path().join("functions.o");
-                    let mut writer = BufWriter::new(File::create(&object_file_path)?);
-                    obj.write_stream(&mut writer)
-                        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
-                    writer.flush()?;
-                    // Write down header file that includes pointer arrays and the deserialize function
-                    let mut writer = BufWriter::new(File::create(&static_defs_header_path)?);
-                    writer.write_all(header_file_src.as_bytes())?;
-                    writer.flush()?;
+                    let start = Instant::now();
+                    super::create_obj::write_symbols_object(
+                        &symbols_object,
+                        &object_file_path,
+                        &static_defs_header_path,
+                    )?;
+                    self.record_phase("write object", start);
+                    self.record_file_size("Wasm object", &object_file_path);
+                    verbose!(
+                        self.verbose,
+                        2,
+                        "header_file_src:\n{}",
+                        symbols_object.header
+                    );
+                    if let Some(asm_path) = self.emit_asm.as_ref() {
+                        write_disassembly(
+                            &object_file_path,
+                            &symbols_object.function_names,
+                            &starting_cd.join(asm_path),
+                        )?;
+                    }
+                    if self.emit_object {
+                        return self.write_emitted_object(
+                            &object_file_path,
+                            &static_defs_header_path,
+                            &output_path,
+                        );
+                    }
                     if let Some(setup) = cross_compilation.as_ref() {
                         self.compile_zig(
                             output_path,
@@ -400,10 +1204,9 @@ impl CreateExe {
                         self.link(
                             static_defs_header_path,
                             LinkCode {
-                                object_paths: vec![object_file_path, "main_obj.obj".into()],
-                                output_path,
-                                working_dir: working_dir.path().to_path_buf(),
-                                ..Default::default()
+                                object_paths: vec![object_file_path],
+                                working_dir: working_dir.clone(),
+                                ..self.link_code(&starting_cd, output_path)?
                             },
                         )?;
                     }
@@ -411,72 +1214,787 @@ impl CreateExe {
             }
         }
 
-        if cross_compilation.is_some() {
+        if self.strip {
+            let start = Instant::now();
+            self.strip_executable(&starting_cd.join(&output))?;
+            self.record_phase("strip", start);
+        }
+
+        if let Some(depfile) = self.depfile.as_ref().filter(|_| !self.dry_run) {
+            let (include_dir, libwasmer_path) = match cross_compilation.as_ref() {
+                // `compile_zig` looks for the headers next to the cross-compilation library.
+                Some(setup) => (
+                    setup
+                        .library
+                        .parent()
+                        .and_then(Path::parent)
+                        .map(|dir| dir.join("include"))
+                        .unwrap_or_default(),
+                    setup.library.clone(),
+                ),
+                None => (
+                    get_wasmer_include_directory()?,
+                    get_libwasmer_path(self.linkage())?,
+                ),
+            };
+            let mut prerequisites = wasm_module_paths
+                .iter()
+                .filter(|path| !path.starts_with(temp_dir.path()))
+                .cloned()
+                .collect::<Vec<_>>();
+            if let Some(header_path) = self.header.as_ref() {
+                prerequisites.push(starting_cd.join(header_path));
+            }
+            prerequisites.push(include_dir.join("wasmer.h"));
+            prerequisites.push(include_dir.join("wasm.h"));
+            prerequisites.push(libwasmer_path);
+            write_depfile(
+                &starting_cd.join(depfile),
+                &starting_cd.join(&output),
+                &prerequisites,
+            )?;
+        }
+
+        if self.dry_run {
+            eprintln!("Dry run: `{}` was not built.", output.display());
+        } else if self.check {
+            eprintln!(
+                "✔ Check passed: an executable for `{}` target was built and discarded.",
+                target.triple(),
+            );
+        } else if cross_compilation.is_some() {
             eprintln!(
                 "✔ Cross-compiled executable for `{}` target compiled successfully to `{}`.",
                 target.triple(),
-                self.output.display(),
+                output.display(),
             );
         } else {
             eprintln!(
-                "✔ Native executable compiled successfully to `{}`.",
-                self.output.display(),
+                "✔ Native {} compiled successfully to `{}` using `{}`.",
+                match self.output_kind {
+                    OutputKind::Executable => "executable",
+                    OutputKind::SharedLibrary => "shared library",
+                },
+                output.display(),
+                c_compiler.display(),
+            );
+        }
+        if !self.dry_run {
+            self.print_sizes(&starting_cd.join(&output));
+        }
+        if kept_intermediate_dir.is_some() {
+            eprintln!(
+                "Intermediate files were kept in `{}`.",
+                working_dir.display()
             );
         }
+        if self.timings {
+            self.print_timings(build_start);
+        }
+
+        if self.run {
+            let status = self.run_executable(&starting_cd)?;
+            // `exit` doesn't run destructors, so clean up the intermediate files first.
+            drop(temp_dir);
+            std::process::exit(status.code().unwrap_or(1));
+        }
 
         Ok(())
     }
 
-    fn compile_zig(
-        &self,
-        output_path: PathBuf,
-        object_path: PathBuf,
-        mut header_path: PathBuf,
-        setup: &CrossCompileSetup,
-    ) -> anyhow::Result<()> {
-        debug_assert!(
-            header_path.is_absolute(),
-            "compile_zig() called with relative header file path {}",
-            header_path.display()
+    /// Records that the build phase `phase`, which began at `start`, just finished.
+    fn record_phase(&self, phase: impl Into<String>, start: Instant) {
+        self.phase_durations
+            .lock()
+            .unwrap()
+            .push((phase.into(), start.elapsed()));
+    }
+
+    /// Prints the durations of the build phases for `--timings`, and the total time since
+    /// `build_start`. Modules are compiled in parallel, so their durations can add up to more.
+    fn print_timings(&self, build_start: Instant) {
+        let phase_durations = self.phase_durations.lock().unwrap();
+        let width = phase_durations
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or_default();
+        println!();
+        for (phase, duration) in phase_durations.iter() {
+            println!(
+                "{:<width$}  {:>10}",
+                phase,
+                format!("{:.2?}", duration),
+                width = width
+            );
+        }
+        println!(
+            "{:<width$}  {:>10}",
+            "total",
+            format!("{:.2?}", build_start.elapsed()),
+            width = width
         );
-        let CrossCompileSetup {
-            ref target,
-            ref zig_binary_path,
-            ref library,
-            ref working_dir,
-        } = setup;
-        let c_src_path = working_dir.join("wasmer_main.c");
-        let mut libwasmer_path = library.to_path_buf();
+    }
 
-        println!("Library Path: {}", libwasmer_path.display());
-        /* Cross compilation is only possible with zig */
-        println!("Using zig binary: {}", zig_binary_path.display());
-        let zig_triple = triple_to_zig_triple(target);
-        eprintln!("Using zig target triple: {}", &zig_triple);
+    /// Records the size of the part `part` of the executable.
+    fn record_size(&self, part: impl Into<String>, size: u64) {
+        self.part_sizes.lock().unwrap().push((part.into(), size));
+    }
 
-        let lib_filename = libwasmer_path
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        libwasmer_path.pop();
-        {
-            let mut c_src_file = fs::OpenOptions::new()
-                .create_new(true)
-                .write(true)
-                .open(&c_src_path)
-                .context("Failed to open C source code file")?;
-            c_src_file.write_all(WASMER_MAIN_C_SOURCE)?;
+    /// Records the size of the file at `path` as the part `part` of the executable, unless
+    /// it doesn't exist, as in a dry run.
+    fn record_file_size(&self, part: impl Into<String>, path: &Path) {
+        if let Ok(metadata) = fs::metadata(path) {
+            self.record_size(part, metadata.len());
         }
+    }
 
-        if !header_path.is_dir() {
-            header_path.pop();
+    /// Prints the size of the executable at `executable_path`, preceded at `-v` by the sizes
+    /// of its parts.
+    fn print_sizes(&self, executable_path: &Path) {
+        let executable_size = match fs::metadata(executable_path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return,
+        };
+        if self.verbose >= 1 {
+            let part_sizes = self.part_sizes.lock().unwrap();
+            let width = part_sizes
+                .iter()
+                .map(|(part, _)| part.len())
+                .max()
+                .unwrap_or_default();
+            for (part, size) in part_sizes.iter() {
+                println!("  {:<width$}  {:>10} bytes", part, size, width = width);
+            }
         }
+        println!("Executable size: {} bytes", executable_size);
+    }
 
-        /* Compile main function */
-        let compilation = {
-            let mut include_dir = libwasmer_path.clone();
+    /// Runs the built executable from `starting_cd` with the arguments given after `--`.
+    fn run_executable(&self, starting_cd: &Path) -> anyhow::Result<std::process::ExitStatus> {
+        let executable_path = starting_cd.join(self.output());
+        verbose!(
+            self.verbose,
+            1,
+            "Running `{}` with arguments {:?}",
+            executable_path.display(),
+            self.runtime_args
+        );
+        Command::new(&executable_path)
+            .args(&self.runtime_args)
+            .current_dir(starting_cd)
+            .status()
+            .with_context(|| format!("Could not run `{}`", executable_path.display()))
+    }
+
+    /// Strips the symbols of the executable at `executable_path`, warning instead of failing
+    /// if no strip tool is available.
+    fn strip_executable(&self, executable_path: &Path) -> anyhow::Result<()> {
+        let strip_tool = match self.strip_tool() {
+            Some(strip_tool) => strip_tool,
+            None => {
+                eprintln!(
+                    "warning: no strip tool was found, `{}` was not stripped; set `WASMER_STRIP` to choose one",
+                    executable_path.display()
+                );
+                return Ok(());
+            }
+        };
+        let mut command = Command::new(&strip_tool);
+        command.arg(executable_path);
+        if self.dry_run {
+            println!("{:?}", command);
+            return Ok(());
+        }
+        let size_before = fs::metadata(executable_path)?.len();
+        let output = command.output()?;
+        if !output.status.success() {
+            bail!(
+                "`{}` failed with: stdout: {}\n\nstderr: {}",
+                strip_tool.display(),
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let size_after = fs::metadata(executable_path)?.len();
+        verbose!(
+            self.verbose,
+            1,
+            "Stripped `{}` with `{}`: {} bytes -> {} bytes",
+            executable_path.display(),
+            strip_tool.display(),
+            size_before,
+            size_after
+        );
+        Ok(())
+    }
+
+    /// Returns the strip tool to use for the target, if one can be run.
+    fn strip_tool(&self) -> Option<PathBuf> {
+        if let Some(strip_tool) = env::var_os("WASMER_STRIP") {
+            return Some(PathBuf::from(strip_tool));
+        }
+        let mut candidates = Vec::new();
+        if let Some(target) = self.target_triple.as_ref() {
+            candidates.push(format!("{}-strip", target));
+        }
+        candidates.push("llvm-strip".to_string());
+        // The host `strip` only understands objects of the host architecture.
+        if self.target_triple.is_none() {
+            candidates.push("strip".to_string());
+        }
+        candidates
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|candidate| Command::new(candidate).arg("--version").output().is_ok())
+    }
+
+    /// Selects the compiler, target and object format from the command line.
+    fn plan(&self) -> anyhow::Result<BuildPlan> {
+        let target = if self.target_triple.is_some() || self.cpu.is_some() {
+            let target_triple = self.target_triple.clone().unwrap_or_else(Triple::host);
+            // The `-m` features are added on top of the baseline of the `--cpu`.
+            let baseline = match self.cpu.as_ref() {
+                Some(cpu) => cpu_baseline(&target_triple.architecture, cpu)?,
+                None => vec![],
+            };
+            let mut features = baseline
+                .into_iter()
+                .chain(self.cpu_features.clone())
+                .fold(CpuFeature::set(), |a, b| a | b);
+            if requires_sse2(&self.compiler.get_compiler()?, &target_triple.architecture) {
+                features |= CpuFeature::SSE2;
+            }
+            Target::new(target_triple, features)
+        } else {
+            Target::from_host_with_features()
+        };
+        let (store, compiler_type) = self.compiler.get_store_for_target(target.clone())?;
+        let features = store.engine().inner().features().clone();
+        Ok(BuildPlan {
+            store,
+            compiler_type,
+            target,
+            object_format: self.object_format.unwrap_or(ObjectFormat::Symbols),
+            features,
+        })
+    }
+
+    /// Checks that the C compiler and the Wasmer headers and library needed for a native build
+    /// are installed, before spending time compiling the Wasm modules.
+    fn preflight_check(&self) -> anyhow::Result<()> {
+        // Cross-compilation takes `libwasmer` from `--library-path` or `--tarball`.
+        if self.target_triple.is_some() {
+            return Ok(());
+        }
+        // `--emit-object` neither compiles nor links any C code.
+        if self.emit_object {
+            return Ok(());
+        }
+        // A dry run doesn't run the C compiler.
+        if !self.dry_run {
+            let (c_compiler, _) = self.c_compiler()?;
+            if let Err(err) = Command::new(&c_compiler).arg("--version").output() {
+                bail!(
+                    "Could not run the C compiler `{}`: {}. Install a C toolchain, or pass the path of a C compiler with `--cc`.",
+                    c_compiler.display(),
+                    err
+                );
+            }
+        }
+        let header_path = get_wasmer_include_directory()?.join("wasmer.h");
+        if !header_path.is_file() {
+            bail!(
+                "Could not find the Wasmer header file at `{}`. Check that `WASMER_DIR` points to a Wasmer installation.",
+                header_path.display()
+            );
+        }
+        // libwasmer is built along with its headers, whose version tells whether its C API
+        // matches the one this CLI generates code for.
+        let header = fs::read_to_string(&header_path)
+            .with_context(|| format!("Could not read `{}`", header_path.display()))?;
+        match wasmer_header_version(&header) {
+            Some(version) if version == crate::VERSION => {}
+            Some(version) if major_minor(version) != major_minor(crate::VERSION) => bail!(
+                "The Wasmer installation at `{}` is version {}, but this CLI is version {}. Check that `WASMER_DIR` points to a Wasmer installation of the same version.",
+                get_wasmer_dir()?.display(),
+                version,
+                crate::VERSION
+            ),
+            Some(version) => eprintln!(
+                "warning: the Wasmer installation at `{}` is version {}, but this CLI is version {}",
+                get_wasmer_dir()?.display(),
+                version,
+                crate::VERSION
+            ),
+            None => verbose!(
+                self.verbose,
+                1,
+                "Could not find the version of `{}`",
+                header_path.display()
+            ),
+        }
+        let libwasmer_path = get_libwasmer_path(self.linkage())?;
+        if !libwasmer_path.is_file() {
+            bail!(
+                "Could not find libwasmer at `{}`. Check that `WASMER_DIR` points to a Wasmer installation{}.",
+                libwasmer_path.display(),
+                if self.linkage() == Linkage::Dynamic {
+                    " that ships the shared library"
+                } else {
+                    ""
+                }
+            );
+        }
+        Ok(())
+    }
+
+    /// Prints the triples accepted by `--target` for the selected compiler, along with their
+    /// default CPU features.
+    fn print_targets(&self) -> anyhow::Result<()> {
+        let (_, compiler_type) = self.compiler.get_store_for_target(Target::default())?;
+        let architectures: &[&str] = match compiler_type {
+            CompilerType::Singlepass | CompilerType::Cranelift => &["x86_64", "aarch64"],
+            CompilerType::LLVM => &["x86_64", "i686", "aarch64"],
+            CompilerType::Headless => bail!("The headless engine cannot compile Wasm modules."),
+        };
+        println!("Compiler: {}", compiler_type.to_string());
+        for architecture in architectures {
+            for system in CROSS_COMPILE_SYSTEMS {
+                let triple = format!("{}-{}", architecture, system);
+                let triple = match Triple::from_str(&triple) {
+                    Ok(triple) => triple,
+                    Err(_) => continue,
+                };
+                let features = if requires_sse2(&compiler_type, &triple.architecture) {
+                    vec![CpuFeature::SSE2.to_string()]
+                } else {
+                    vec![]
+                };
+                if features.is_empty() {
+                    println!("{}", triple);
+                } else {
+                    println!("{} (default CPU features: {})", triple, features.join(", "));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns how `libwasmer` should be linked, given the `--static`/`--dynamic` flags.
+    fn linkage(&self) -> Linkage {
+        if self.link_dynamic && !self.link_static {
+            Linkage::Dynamic
+        } else {
+            Linkage::Static
+        }
+    }
+
+    /// Returns the C compiler given with `--cc` or the `WASMER_CC` or `CC` environment
+    /// variables, falling back to the platform default, along with the arguments it must be
+    /// run with first, e.g. `cc` for `CC="ccache cc"`.
+    fn c_compiler(&self) -> anyhow::Result<(PathBuf, Vec<String>)> {
+        if let Some(cc) = self
+            .cc
+            .clone()
+            .or_else(|| env::var_os("WASMER_CC").map(PathBuf::from))
+        {
+            return Ok((cc, vec![]));
+        }
+        Ok(env_command("CC")?.unwrap_or_else(|| (default_c_compiler(), vec![])))
+    }
+
+    /// Returns the absolute paths of the input files. An input of `-` is read from stdin and
+    /// stored in `temp_dir`.
+    fn input_paths(&self, starting_cd: &Path, temp_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let stdin_path = Path::new("-");
+        if self.path.iter().filter(|path| *path == stdin_path).count() > 1 {
+            bail!("The input can only be read from stdin (`-`) once.");
+        }
+        self.path
+            .iter()
+            .map(|path| {
+                if path != stdin_path {
+                    return Ok(starting_cd.join(path));
+                }
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .read_to_end(&mut bytes)
+                    .context("Could not read the input from stdin")?;
+                let stdin_input_path = temp_dir.join("stdin.wasm");
+                fs::write(&stdin_input_path, &bytes)?;
+                Ok(stdin_input_path)
+            })
+            .collect()
+    }
+
+    /// Compiles and serializes the Wasm module at `wasm_module_path`, or reuses a previously
+    /// serialized copy found in `precompiled_dir`.
+    fn serialize_module(
+        &self,
+        store: &Store,
+        compiler: &str,
+        wasm_module_path: &Path,
+        target: &Target,
+        precompiled_dir: Option<&Path>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let wasm_bytes = fs::read(wasm_module_path)
+            .with_context(|| format!("Could not read `{}`", wasm_module_path.display()))?;
+        let precompiled_path = precompiled_dir.map(|dir| {
+            dir.join(precompiled_file_name(
+                wasm_module_path,
+                &wasm_bytes,
+                target,
+                compiler,
+                store.engine().inner().features(),
+            ))
+        });
+
+        if let Some(precompiled_path) = precompiled_path.as_ref() {
+            match fs::read(precompiled_path) {
+                Ok(bytes) if is_complete_serialized_module(&bytes) => {
+                    verbose!(
+                        self.verbose,
+                        1,
+                        "Using precompiled module `{}`",
+                        precompiled_path.display()
+                    );
+                    return Ok(bytes);
+                }
+                Ok(_) => verbose!(
+                    self.verbose,
+                    1,
+                    "Ignoring corrupted precompiled module `{}`",
+                    precompiled_path.display()
+                ),
+                Err(_) => {}
+            }
+        }
+
+        let module = Module::new(store, &wasm_bytes)
+            .map_err(|err| compile_error_context(err, wasm_module_path, compiler, target))?;
+        let bytes = module.serialize()?.to_vec();
+
+        if let Some(precompiled_path) = precompiled_path.as_ref() {
+            let written = precompiled_path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| write_atomically(precompiled_path, &bytes));
+            match written {
+                Ok(()) => {}
+                // The default cache is only an optimization.
+                Err(err) if self.precompiled_atom.is_none() => verbose!(
+                    self.verbose,
+                    1,
+                    "Could not cache the serialized module in `{}`: {}",
+                    precompiled_path.display(),
+                    err
+                ),
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!(
+                            "Could not write precompiled module to `{}`",
+                            precompiled_path.display()
+                        )
+                    })
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the C glue code compiled with the Wasm object: the executable's `main`, or the
+    /// functions exported by a shared library.
+    fn glue_c_source(&self) -> String {
+        match self.output_kind {
+            OutputKind::Executable => self.main_c_source(),
+            OutputKind::SharedLibrary => WASMER_LIB_C_SOURCE.to_string(),
+        }
+    }
+
+    /// Returns the C source of the executable's `main`, preceded by the name of a single
+    /// embedded module, the WASI defaults given with `--mapdir`, `--env` and `--arg`, and the
+    /// WASI functions allowed with `--allow`.
+    fn main_c_source(&self) -> String {
+        let mapdirs = self
+            .mapped_dirs
+            .iter()
+            .flat_map(|(alias, dir)| vec![alias.clone(), dir.to_string_lossy().into_owned()]);
+        let env_vars = self
+            .env_vars
+            .iter()
+            .flat_map(|(key, value)| vec![key.clone(), value.clone()]);
+        let mut glue = CGlue::default();
+        glue.include("<stddef.h>")
+            .raw("\n")
+            .define_value("WASMER_MODULE_NAME", c_string_literal(SINGLE_MODULE_NAME))
+            .string_array("wasmer_default_mapdirs", mapdirs)
+            .string_array("wasmer_default_env", env_vars)
+            .string_array("wasmer_default_args", self.wasi_args.iter().cloned());
+        if let Some(capabilities) = self.allowed_wasi_capabilities.as_ref() {
+            glue.define("WASMER_RESTRICT_WASI").string_array(
+                "wasmer_allowed_wasi_imports",
+                ALWAYS_ALLOWED_WASI_IMPORTS
+                    .iter()
+                    .chain(
+                        capabilities
+                            .iter()
+                            .flat_map(|capability| wasi_capability_imports(capability)),
+                    )
+                    .map(|import| import.to_string()),
+            );
+        }
+        glue.raw("\n").raw(WASMER_MAIN_C_SOURCE);
+        glue.into_source()
+    }
+
+    /// Returns the path of the executable: the `-o` path, or a file named after the first input
+    /// in it if it is a directory. Windows executables get an `.exe` extension if they have
+    /// none, unless `--no-exe-extension` is given; shared libraries always get the target's
+    /// extension for them if they have none.
+    ///
+    /// With `--emit-object`, this is the path of the object file instead.
+    fn output(&self) -> PathBuf {
+        let output = self
+            .output
+            .as_deref()
+            .expect("clap requires `-o` unless `--list-targets` is given");
+        if !output.is_dir() {
+            let keep_name = self.emit_object
+                || (self.output_kind == OutputKind::Executable && self.no_exe_extension)
+                || output.extension().is_some();
+            return match self.output_extension() {
+                Some(extension) if !keep_name => output.with_extension(extension),
+                _ => output.to_path_buf(),
+            };
+        }
+        let mut name = self
+            .path
+            .first()
+            .filter(|path| *path != Path::new("-"))
+            .and_then(|path| path.file_stem())
+            .unwrap_or_else(|| std::ffi::OsStr::new("wasm"))
+            .to_os_string();
+        if let Some(extension) = self.output_extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        output.join(name)
+    }
+
+    /// The extension of the built file on the target, if it has one.
+    fn output_extension(&self) -> Option<&'static str> {
+        let windows = self.targets_windows();
+        if self.emit_object {
+            return Some(if windows { "obj" } else { "o" });
+        }
+        match self.output_kind {
+            OutputKind::Executable if windows => Some("exe"),
+            OutputKind::Executable => None,
+            OutputKind::SharedLibrary if windows => Some("dll"),
+            OutputKind::SharedLibrary => {
+                let triple = self.target_triple.clone().unwrap_or_else(Triple::host);
+                if triple.binary_format == wasmer_types::BinaryFormat::Macho {
+                    Some("dylib")
+                } else {
+                    Some("so")
+                }
+            }
+        }
+    }
+
+    /// Copies the object file at `object_path` to `output_path` for `--emit-object`, and its
+    /// header at `header_path` next to it.
+    fn write_emitted_object(
+        &self,
+        object_path: &Path,
+        header_path: &Path,
+        output_path: &Path,
+    ) -> Result<()> {
+        let output_header_path = output_path.with_extension("h");
+        fs::copy(object_path, output_path)
+            .with_context(|| format!("Could not write `{}`", output_path.display()))?;
+        fs::copy(header_path, &output_header_path)
+            .with_context(|| format!("Could not write `{}`", output_header_path.display()))?;
+        eprintln!(
+            "✔ Object file written to `{}`, with its header `{}`.",
+            output_path.display(),
+            output_header_path.display(),
+        );
+        Ok(())
+    }
+
+    /// Whether the executable is built for Windows.
+    fn targets_windows(&self) -> bool {
+        self.target_triple
+            .clone()
+            .unwrap_or_else(Triple::host)
+            .operating_system
+            == wasmer_types::OperatingSystem::Windows
+    }
+
+    /// Whether the executable is linked as a PIE, or `None` to keep the toolchain's default.
+    ///
+    /// Only Linux targets are built as PIE explicitly.
+    fn pie(&self) -> Option<bool> {
+        let triple = self.target_triple.clone().unwrap_or_else(Triple::host);
+        if self.output_kind == OutputKind::SharedLibrary {
+            // The code of a shared library must be position-independent, except in Windows
+            // DLLs, which are relocated when loaded.
+            return if triple.operating_system == wasmer_types::OperatingSystem::Windows {
+                None
+            } else {
+                Some(true)
+            };
+        }
+        if triple.operating_system == wasmer_types::OperatingSystem::Linux {
+            Some(!self.no_pie)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the executable is built reproducibly, with `--reproducible` or
+    /// `SOURCE_DATE_EPOCH`.
+    fn is_reproducible(&self) -> bool {
+        self.reproducible || env::var_os("SOURCE_DATE_EPOCH").is_some()
+    }
+
+    /// Returns the arguments given with `--cc-arg` for C code compiled in `working_dir`,
+    /// preceded in reproducible builds by a flag leaving `working_dir` out of the object.
+    fn cc_args_in(&self, working_dir: &Path) -> Vec<String> {
+        let mut cc_args = Vec::with_capacity(self.cc_args.len() + 1);
+        if self.is_reproducible() {
+            cc_args.push(format!("-ffile-prefix-map={}=.", working_dir.display()));
+        }
+        cc_args.extend(self.cc_args.iter().cloned());
+        cc_args
+    }
+
+    /// Returns the `-O` flag for the optimization level given with `--optimization-level`.
+    fn optimization_flag(&self) -> String {
+        format!("-O{}", self.optimization_level)
+    }
+
+    /// Returns the linking options shared by all the ways of linking the executable.
+    fn link_code(&self, starting_cd: &Path, output_path: PathBuf) -> anyhow::Result<LinkCode> {
+        let (linker_path, linker_args, linker_kind) = match self.linker.as_ref() {
+            Some(linker) => (linker.clone(), vec![], LinkerKind::from_linker_path(linker)),
+            None => match env_command("LD")? {
+                Some((linker, linker_args)) => {
+                    let linker_kind = LinkerKind::from_linker_path(&linker);
+                    (linker, linker_args, linker_kind)
+                }
+                None => {
+                    let (c_compiler, c_compiler_args) = self.c_compiler()?;
+                    (c_compiler, c_compiler_args, LinkerKind::CompilerDriver)
+                }
+            },
+        };
+        Ok(LinkCode {
+            linker_path,
+            linker_args,
+            linker_kind,
+            output_path,
+            optimization_flag: self.optimization_flag(),
+            debug: self.debug,
+            pie: self.pie(),
+            linkage: self.linkage(),
+            verbose: self.verbose,
+            dry_run: self.dry_run,
+            // Relative directories are relative to where `create-exe` is run from, not to the
+            // working directory it links in.
+            library_paths: self
+                .library_paths
+                .iter()
+                .map(|dir| starting_cd.join(dir))
+                .collect(),
+            // Archives named by `-l` are found from where `create-exe` is run too.
+            additional_libraries: self
+                .libraries
+                .iter()
+                .map(|lib| {
+                    let path = starting_cd.join(lib);
+                    if path.is_file() {
+                        path.to_string_lossy().into_owned()
+                    } else {
+                        lib.clone()
+                    }
+                })
+                .collect(),
+            default_libraries: !self.no_default_libs,
+            extra_args: self.linker_args.clone(),
+            gc_sections: !self.no_gc_sections,
+            strict_c: self.strict_c,
+            map_path: self.map.as_ref().map(|path| starting_cd.join(path)),
+            shared: self.output_kind == OutputKind::SharedLibrary,
+            reproducible: self.is_reproducible(),
+            ..Default::default()
+        })
+    }
+
+    fn compile_zig(
+        &self,
+        output_path: PathBuf,
+        object_path: PathBuf,
+        mut header_path: PathBuf,
+        setup: &CrossCompileSetup,
+    ) -> anyhow::Result<()> {
+        debug_assert!(
+            header_path.is_absolute(),
+            "compile_zig() called with relative header file path {}",
+            header_path.display()
+        );
+        let CrossCompileSetup {
+            ref target,
+            ref zig_binary_path,
+            ref library,
+            ref working_dir,
+        } = setup;
+        let c_src_path = working_dir.join("wasmer_main.c");
+        let mut libwasmer_path = library.to_path_buf();
+
+        verbose!(
+            self.verbose,
+            1,
+            "Library Path: {}",
+            libwasmer_path.display()
+        );
+        /* Cross compilation is only possible with zig */
+        verbose!(
+            self.verbose,
+            1,
+            "Using zig binary: {}",
+            zig_binary_path.display()
+        );
+        let zig_triple = triple_to_zig_triple(target);
+        verbose!(self.verbose, 1, "Using zig target triple: {}", &zig_triple);
+
+        let lib_filename = libwasmer_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        libwasmer_path.pop();
+        {
+            let mut c_src_file = fs::OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&c_src_path)
+                .context("Failed to open C source code file")?;
+            c_src_file.write_all(self.main_c_source().as_bytes())?;
+        }
+
+        if !header_path.is_dir() {
+            header_path.pop();
+        }
+
+        /* Compile main function */
+        let compilation = {
+            let mut include_dir = libwasmer_path.clone();
             include_dir.pop();
             include_dir.push("include");
 
@@ -488,7 +2006,19 @@ impl CreateExe {
             let mut cmd = Command::new(zig_binary_path);
             let mut cmd_mut: &mut Command = cmd
                 .arg(compiler_cmd)
-                .arg("-w")
+                .arg(self.optimization_flag())
+                .args(debug_flags(self.debug))
+                .args(function_sections_flags(!self.no_gc_sections))
+                .args(gc_sections_flags(
+                    !self.no_gc_sections,
+                    LinkerKind::CompilerDriver,
+                    target,
+                ))
+                .args(if self.strict_c {
+                    warning_flags(true)
+                } else {
+                    &["-w"][..]
+                })
                 .arg("-fgnu-inline-asm")
                 .arg("-fsanitize=undefined")
                 .arg("-fsanitize-trap=undefined")
@@ -502,102 +2032,735 @@ impl CreateExe {
                 cmd_mut = cmd_mut.arg("-lunwind");
             }
             cmd_mut
+                .args(&self.cc_args_in(working_dir))
+                .args(&self.linker_args)
                 .arg(&object_path)
                 .arg(&c_src_path)
                 .arg("-o")
-                .arg(&output_path)
-                .output()
-                .context("Could not execute `zig`")?
+                .arg(&output_path);
+            let start = Instant::now();
+            let compilation =
+                run_command(cmd_mut, self.dry_run).context("Could not compile with `zig`")?;
+            self.record_phase("compile C glue code and link", start);
+            compilation
         };
-        if !compilation.status.success() {
-            return Err(anyhow::anyhow!(String::from_utf8_lossy(
-                &compilation.stderr
-            )
-            .to_string()));
+        if let Some(compilation) = compilation {
+            verbose!(
+                self.verbose,
+                2,
+                "{}",
+                String::from_utf8_lossy(&compilation.stderr)
+            );
+        }
+        Ok(())
+    }
+
+    /// Compiles the C glue code against the header at `header_path`, then links it together
+    /// with the objects of `linkcode`.
+    #[cfg(feature = "static-artifact-create")]
+    fn link(&self, mut header_path: PathBuf, mut linkcode: LinkCode) -> anyhow::Result<()> {
+        debug_assert!(
+            header_path.is_absolute(),
+            "link() called with relative header file path {}",
+            header_path.display()
+        );
+        let c_src_path: PathBuf = linkcode.working_dir.join("wasmer_main.c");
+        let main_obj_path: PathBuf = linkcode.working_dir.join("main_obj.obj");
+        {
+            let mut c_src_file = fs::OpenOptions::new()
+                .create(true)
+                .truncate(true)
+                .write(true)
+                .open(&c_src_path)
+                .context("Failed to open C source code file")?;
+            c_src_file.write_all(self.glue_c_source().as_bytes())?;
+        }
+
+        if !header_path.is_dir() {
+            header_path.pop();
+        }
+
+        /* Compile main function */
+        let start = Instant::now();
+        let compilation = {
+            let (c_compiler, c_compiler_args) = self.c_compiler()?;
+            let mut command = Command::new(c_compiler);
+            command.args(c_compiler_args);
+            command
+                .arg("-c")
+                .arg(&c_src_path)
+                .arg(&linkcode.optimization_flag)
+                .args(debug_flags(linkcode.debug))
+                .args(pic_flags(linkcode.pie))
+                .args(function_sections_flags(linkcode.gc_sections))
+                .args(warning_flags(linkcode.strict_c))
+                .arg(&format!("-I{}", get_wasmer_include_directory()?.display()))
+                .arg(&format!("-I{}", header_path.display()))
+                .args(env_words("CFLAGS")?)
+                .args(&self.cc_args_in(&linkcode.working_dir))
+                .args(if linkcode.verbose >= 2 {
+                    &["-v"][..]
+                } else {
+                    &[]
+                })
+                .arg("-o")
+                .arg(&main_obj_path);
+            run_command(&mut command, linkcode.dry_run)
+                .context("Failed to compile C source code")?
+        };
+        self.record_phase("compile C glue code", start);
+        self.record_file_size("C glue object", &main_obj_path);
+        if let Some(compilation) = compilation {
+            verbose!(
+                self.verbose,
+                2,
+                "{}",
+                String::from_utf8_lossy(&compilation.stderr)
+            );
         }
+        linkcode.object_paths.push(main_obj_path);
+        let start = Instant::now();
+        linkcode.run().context("Failed to link objects together")?;
+        self.record_phase("link", start);
         Ok(())
     }
+}
+
+/// Symbols and metadata an object file must provide to match a header from `create-obj`.
+#[derive(Debug, Default)]
+struct HeaderRequirements {
+    /// Symbols declared `extern` in the header.
+    symbols: Vec<String>,
+    /// Value of `module_bytes_len`, the length of `WASMER_METADATA`, for headers of objects
+    /// in the `symbols` format.
+    metadata_length: Option<u64>,
+    /// Name of the `WASMER_METADATA` symbol, which carries the symbol prefix of the object.
+    metadata_symbol: Option<String>,
+}
+
+impl HeaderRequirements {
+    fn parse(header: &str) -> Self {
+        let mut requirements = Self::default();
+        // Prefix of the symbols declared with an `asm` label, see `serialized_header`. The
+        // first definition wins, as with the `#ifndef` guarding the default.
+        let mut symbol_prefix: Option<&str> = None;
+        // Macros defined so far, and whether the condition of each enclosing `#ifdef` or
+        // `#ifndef` holds, so that declarations in the `WASMER_COMPRESSED_MODULES` branches
+        // only count when it is defined. Other conditions are assumed to hold.
+        let mut defined = std::collections::HashSet::new();
+        let mut conditions: Vec<bool> = vec![];
+        for line in header.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("#ifdef ") {
+                conditions.push(defined.contains(name.trim()));
+                continue;
+            }
+            if let Some(name) = line.strip_prefix("#ifndef ") {
+                conditions.push(!defined.contains(name.trim()));
+                continue;
+            }
+            if line.starts_with("#if") {
+                conditions.push(true);
+                continue;
+            }
+            if line.starts_with("#else") {
+                if let Some(condition) = conditions.last_mut() {
+                    *condition = !*condition;
+                }
+                continue;
+            }
+            if line.starts_with("#endif") {
+                conditions.pop();
+                continue;
+            }
+            if conditions.contains(&false) {
+                continue;
+            }
+            if let Some(definition) = line.strip_prefix("#define ") {
+                let mut definition = definition.trim().splitn(2, char::is_whitespace);
+                let name = definition.next().unwrap_or_default();
+                if name == "WASMER_SYMBOL_PREFIX" {
+                    symbol_prefix.get_or_insert(definition.next().unwrap_or_default().trim());
+                }
+                defined.insert(name);
+                continue;
+            }
+            if let Some(value) = line
+                .strip_prefix("const unsigned int module_bytes_len = ")
+                .and_then(|rest| rest.strip_suffix(';'))
+            {
+                requirements.metadata_length = value.trim().parse().ok();
+                continue;
+            }
+            let declaration = match line.find("extern ") {
+                Some(start) if !line.contains("extern \"C\"") => &line[start..],
+                _ => continue,
+            };
+            let declaration = declaration
+                .split(|c| c == '(' || c == '[' || c == ';')
+                .next()
+                .unwrap_or_default();
+            let (declaration, prefix) = match declaration.strip_suffix(" asm") {
+                Some(declaration) => (declaration, symbol_prefix.unwrap_or_default()),
+                None => (declaration, ""),
+            };
+            if let Some(name) = declaration
+                .trim_end()
+                .rsplit(|c: char| c.is_whitespace() || c == '*')
+                .next()
+                .filter(|name| !name.is_empty())
+            {
+                let name = format!("{}{}", prefix, name);
+                if name.starts_with("WASMER_METADATA") {
+                    requirements.metadata_symbol = Some(name.clone());
+                }
+                requirements.symbols.push(name);
+            }
+        }
+        requirements
+    }
+}
+
+/// Checks that the header given with `--header` was generated for the object file given as
+/// input, so that mismatched pairs fail early instead of with linker errors.
+fn validate_header_matches_object(header_path: &Path, object_path: &Path) -> anyhow::Result<()> {
+    use object::{BinaryFormat, Object, ObjectSymbol};
+
+    let header = fs::read_to_string(header_path)
+        .with_context(|| format!("Could not read header file `{}`", header_path.display()))?;
+    let requirements = HeaderRequirements::parse(&header);
+
+    let object_data = fs::read(object_path)
+        .with_context(|| format!("Could not read object file `{}`", object_path.display()))?;
+    let object_file = object::File::parse(&*object_data)
+        .with_context(|| format!("Could not parse object file `{}`", object_path.display()))?;
+    let is_macho = object_file.format() == BinaryFormat::MachO;
+    let defined = object_file
+        .symbols()
+        .filter(|symbol| symbol.is_definition())
+        .filter_map(|symbol| {
+            let name = symbol.name().ok()?;
+            let name = if is_macho {
+                name.strip_prefix('_').unwrap_or(name)
+            } else {
+                name
+            };
+            Some((name.to_string(), symbol.size()))
+        })
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let missing = requirements
+        .symbols
+        .iter()
+        .filter(|symbol| !defined.contains_key(symbol.as_str()))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        bail!(
+            "The header file `{}` does not match the object file `{}`: the object does not define {}. Were they generated by the same `create-obj` invocation?",
+            header_path.display(),
+            object_path.display(),
+            missing
+                .iter()
+                .map(|symbol| format!("`{}`", symbol))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    if let (Some(expected), Some(&actual)) = (
+        requirements.metadata_length,
+        requirements
+            .metadata_symbol
+            .as_ref()
+            .and_then(|symbol| defined.get(symbol)),
+    ) {
+        // Not every object format records symbol sizes.
+        if actual != 0 && actual != expected {
+            bail!(
+                "The header file `{}` does not match the object file `{}`: it expects {} bytes of module metadata but the object holds {}.",
+                header_path.display(),
+                object_path.display(),
+                expected,
+                actual
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `path` holds a Wasm module `create-exe` can compile, so
+/// that other inputs are reported clearly instead of failing deep in the pipeline.
+fn check_wasm_input(path: &Path) -> anyhow::Result<()> {
+    let bytes = fs::read(path).with_context(|| format!("Could not read `{}`", path.display()))?;
+    if is_wasm(&bytes) {
+        return Ok(());
+    }
+
+    let is_wat = {
+        let text = bytes
+            .iter()
+            .skip_while(|byte| byte.is_ascii_whitespace())
+            .take(2)
+            .copied()
+            .collect::<Vec<u8>>();
+        text.starts_with(b"(") || text.starts_with(b";;")
+    };
+    let detected = if is_wat {
+        if cfg!(feature = "wat") {
+            return Ok(());
+        }
+        "looks like a .wat text file, but this wasmer was built without the `wat` feature; compile it to .wasm first (e.g. with `wat2wasm`)"
+    } else if bytes.starts_with(b"\x7fELF")
+        || bytes.starts_with(&[0xcf, 0xfa, 0xed, 0xfe])
+        || bytes.starts_with(&[0xce, 0xfa, 0xed, 0xfe])
+        || bytes.starts_with(&[0x64, 0x86])
+        || bytes.starts_with(&[0x64, 0xaa])
+        || bytes.starts_with(&[0x4c, 0x01])
+    {
+        "looks like an object file; if it was made by `wasmer create-obj`, pass its header with `--header`"
+    } else if bytes.starts_with(b"!<arch>\n") {
+        "looks like a static library archive, not a Wasm module"
+    } else {
+        "does not start with the Wasm magic bytes `\\0asm`"
+    };
+    bail!("The input file `{}` {}.", path.display(), detected)
+}
+
+/// Returns the names of the Wasm proposals enabled in `features`, as spelled by the
+/// `--enable-*` flags.
+fn enabled_features(features: &Features) -> Vec<&'static str> {
+    let Features {
+        threads,
+        reference_types,
+        simd,
+        bulk_memory,
+        multi_value,
+        tail_call,
+        module_linking,
+        multi_memory,
+        memory64,
+        exceptions,
+        relaxed_simd,
+        extended_const,
+    } = *features;
+    [
+        ("threads", threads),
+        ("reference-types", reference_types),
+        ("simd", simd),
+        ("bulk-memory", bulk_memory),
+        ("multi-value", multi_value),
+        ("tail-call", tail_call),
+        ("module-linking", module_linking),
+        ("multi-memory", multi_memory),
+        ("memory64", memory64),
+        ("exceptions", exceptions),
+        ("relaxed-simd", relaxed_simd),
+        ("extended-const", extended_const),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect()
+}
+
+/// Writes the JSON manifest requested with `--emit-manifest`.
+fn write_manifest(
+    manifest_path: &Path,
+    modules: &[EmbeddedModule],
+    triple: &Triple,
+    compiler: &str,
+    features: &Features,
+) -> anyhow::Result<()> {
+    let modules = modules
+        .iter()
+        .map(|module| {
+            serde_json::json!({
+                "name": module.name,
+                "symbol": module.symbol,
+                "source": module.source.display().to_string(),
+                "length": module.length,
+            })
+        })
+        .collect::<Vec<_>>();
+    let manifest = serde_json::json!({
+        "target": triple.to_string(),
+        "compiler": compiler,
+        "features": enabled_features(features),
+        "modules": modules,
+    });
+    let mut writer = BufWriter::new(File::create(manifest_path).with_context(|| {
+        format!(
+            "Could not create manifest file `{}`",
+            manifest_path.display()
+        )
+    })?);
+    serde_json::to_writer_pretty(&mut writer, &manifest)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns the file name under which the serialized form of `wasm_bytes` is kept in a
+/// `--precompiled-atom` directory or the cache.
+fn precompiled_file_name(
+    wasm_module_path: &Path,
+    wasm_bytes: &[u8],
+    target: &Target,
+    compiler: &str,
+    features: &Features,
+) -> String {
+    let name = wasm_module_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(wasm_bytes);
+    hasher.update(target.triple().to_string().as_bytes());
+    for cpu_feature in target.cpu_features().iter() {
+        hasher.update(cpu_feature.to_string().as_bytes());
+    }
+    hasher.update(compiler.as_bytes());
+    hasher.update(enabled_features(features).join(",").as_bytes());
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    format!("{}-{}.bin", name, hasher.finalize().to_hex())
+}
+
+/// Returns the name selecting the module at `path` on the executable's command line.
+fn module_name_from_path(path: &Path) -> anyhow::Result<String> {
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        bail!(
+            "The file name of `{}` can't be used as a module name: only ASCII letters, digits, `-`, `_` and `.` are allowed.",
+            path.display()
+        );
+    }
+    Ok(name.to_string())
+}
+
+/// Generates the header defining `wasmer_object_module_new` for several serialized modules,
+/// which picks the module to deserialize by its name.
+fn generate_dispatch_header(
+    modules: &[EmbeddedModule],
+    compressed: bool,
+) -> anyhow::Result<String> {
+    let mut names = std::collections::HashSet::new();
+    if let Some(module) = modules.iter().find(|module| !names.insert(&module.name)) {
+        bail!(
+            "Several input files would embed a module named `{}`; module names are taken from the file stems and must be unique.",
+            module.name
+        );
+    }
+
+    let mut header = CGlue::default();
+    if compressed {
+        header.raw(WASMER_DECOMPRESS_HEADER);
+    }
+    header
+        .include("\"wasmer.h\"")
+        .include("<assert.h>")
+        .include("<stdint.h>")
+        .include("<stdio.h>")
+        .include("<stdlib.h>")
+        .include("<string.h>")
+        .raw("\n")
+        .define("WASMER_MODULE_DISPATCH")
+        .raw("\n")
+        .begin_extern_c();
+    let mut symbols = std::collections::HashSet::new();
+    for module in modules
+        .iter()
+        .filter(|module| symbols.insert(&module.symbol))
+    {
+        header.raw(&format!(
+            "extern size_t {0}_LENGTH asm(\"{0}_LENGTH\");\nextern char {0}_DATA asm(\"{0}_DATA\");\n",
+            module.symbol
+        ));
+        if compressed {
+            header.raw(&format!(
+                "extern size_t {0}_ORIGINAL_LENGTH asm(\"{0}_ORIGINAL_LENGTH\");\n",
+                module.symbol
+            ));
+        }
+    }
+
+    header.raw("\n").string_array(
+        "wasmer_module_names",
+        modules.iter().map(|module| module.name.clone()),
+    );
+
+    header.raw(
+        "\nwasm_module_t* wasmer_object_module_new(wasm_store_t* store, const char* module_name) {\n  wasm_byte_vec_t module_byte_vec;\n",
+    );
+    if compressed {
+        header.raw("  size_t original_length;\n");
+    }
+    header.raw(" ");
+    for module in modules {
+        header.raw(&format!(
+            " if (strcmp(module_name, \"{0}\") == 0) {{\n    module_byte_vec.size = {1}_LENGTH;\n    module_byte_vec.data = (const char*)&{1}_DATA;\n",
+            module.name, module.symbol
+        ));
+        if compressed {
+            header.raw(&format!(
+                "    original_length = {}_ORIGINAL_LENGTH;\n",
+                module.symbol
+            ));
+        }
+        header.raw("  } else");
+    }
+    header.raw(" {\n    return NULL;\n  }\n");
+    if compressed {
+        header.raw(&format!(
+            "  return wasmer_module_deserialize_compressed(store, module_byte_vec.data, module_byte_vec.size, original_length, {});\n",
+            MetadataHeader::ALIGN
+        ));
+    } else {
+        header.raw(&format!(
+            "  assert((uintptr_t)module_byte_vec.data % {} == 0);\n  return wasm_module_deserialize(store, &module_byte_vec);\n",
+            MetadataHeader::ALIGN
+        ));
+    }
+    header.raw("}\n\n").end_extern_c();
+    Ok(header.into_source())
+}
+
+/// Returns whether `bytes` is a whole module serialized by this version of Wasmer, rather than,
+/// say, one truncated by an interrupted build.
+fn is_complete_serialized_module(bytes: &[u8]) -> bool {
+    // `MetadataHeader::parse` wants an aligned header.
+    #[repr(C, align(16))]
+    struct AlignedHeader([u8; MetadataHeader::LEN]);
+
+    if !wasmer_compiler::ArtifactBuild::is_deserializable(bytes) {
+        return false;
+    }
+    let header_start = wasmer_compiler::ArtifactBuild::MAGIC_HEADER.len();
+    let metadata_start = header_start + MetadataHeader::LEN;
+    let mut header = AlignedHeader([0; MetadataHeader::LEN]);
+    match bytes.get(header_start..metadata_start) {
+        Some(bytes) => header.0.copy_from_slice(bytes),
+        None => return false,
+    }
+    match MetadataHeader::parse(&header.0) {
+        Ok(len) => bytes.len() == metadata_start + len,
+        Err(_) => false,
+    }
+}
+
+/// Writes `bytes` to `path` so that other processes see either the previous file or the whole
+/// new one, never a partly written one.
+fn write_atomically(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(bytes)?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// The Wasmer cache directory used when `--cache-dir` isn't given.
+fn default_cache_dir() -> PathBuf {
+    if env::var_os("WASMER_CACHE_DIR").is_none() {
+        if let Some(dir) = dirs::cache_dir() {
+            return dir.join("wasmer").join(crate::VERSION);
+        }
+    }
+    get_cache_dir()
+}
+
+/// Writes a Makefile dependency file at `depfile_path` listing `prerequisites` as those of
+/// `target`.
+fn write_depfile(
+    depfile_path: &Path,
+    target: &Path,
+    prerequisites: &[PathBuf],
+) -> anyhow::Result<()> {
+    let escape = |path: &Path| {
+        path.display()
+            .to_string()
+            .replace('$', "$$")
+            .replace('#', "\\#")
+            .replace(' ', "\\ ")
+    };
+    let mut depfile = format!("{}:", escape(target));
+    for prerequisite in prerequisites {
+        depfile.push_str(" \\\n  ");
+        depfile.push_str(&escape(prerequisite));
+    }
+    depfile.push('\n');
+    fs::write(depfile_path, depfile).with_context(|| {
+        format!(
+            "Could not write the dependency file `{}`",
+            depfile_path.display()
+        )
+    })
+}
+
+/// Returns `dir`, created if needed, if serialized modules can be cached in it, or warns that
+/// caching is disabled.
+fn writable_cache_dir(dir: PathBuf) -> Option<PathBuf> {
+    match fs::create_dir_all(&dir).and_then(|()| tempfile::tempfile_in(&dir)) {
+        Ok(_) => Some(dir),
+        Err(err) => {
+            eprintln!(
+                "warning: the cache directory `{}` is not writable, so modules are not cached: {}",
+                dir.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Returns an empty object file for `triple`, explaining which targets object files can be
+/// written for if it isn't one of them.
+pub(crate) fn object_for_target(triple: &Triple) -> Result<Object> {
+    get_object_for_target(triple).with_context(|| {
+        format!(
+            "Could not write a {} object file for `{}`: object files can only be written in the ELF, Mach-O and COFF formats, for the x86_64 and aarch64 architectures",
+            triple.binary_format, triple
+        )
+    })
+}
+
+/// Describes a failure to compile the module at `wasm_module_path` with the given compiler and
+/// target, telling invalid modules apart from failures of the compiler itself.
+pub(crate) fn compile_error_context(
+    err: CompileError,
+    wasm_module_path: &Path,
+    compiler: &str,
+    target: &Target,
+) -> anyhow::Error {
+    let stage = match err {
+        CompileError::Wasm(_) | CompileError::Validate(_) => "parse and validate",
+        _ => "compile",
+    };
+    anyhow::Error::new(err).context(format!(
+        "Failed to {} `{}` with the {} compiler for `{}`",
+        stage,
+        wasm_module_path.display(),
+        compiler,
+        target.triple()
+    ))
+}
+
+/// Returns the header for an object in the `serialized` format whose module is emitted as
+/// `{symbol_prefix}WASMER_MODULE`, and zstd-compressed if `compressed`.
+pub(crate) fn serialized_header(symbol_prefix: Option<&str>, compressed: bool) -> String {
+    let mut header = CGlue::default();
+    if let Some(prefix) = symbol_prefix {
+        header.define_value("WASMER_SYMBOL_PREFIX", prefix);
+    }
+    header.define_value("WASMER_MODULE_ALIGN", MetadataHeader::ALIGN);
+    if compressed {
+        header.raw(WASMER_DECOMPRESS_HEADER);
+    }
+    header.raw(WASMER_DESERIALIZE_HEADER);
+    header.into_source()
+}
 
-    #[cfg(feature = "static-artifact-create")]
-    fn link(&self, mut header_path: PathBuf, linkcode: LinkCode) -> anyhow::Result<()> {
-        debug_assert!(
-            header_path.is_absolute(),
-            "link() called with relative header file path {}",
-            header_path.display()
-        );
-        let c_src_path: PathBuf = linkcode.working_dir.join("wasmer_main.c");
-        let mut libwasmer_path = get_libwasmer_path()?
-            .canonicalize()
-            .context("Failed to find libwasmer")?;
+/// The WASI functions left to the module whatever the capabilities given with `--allow`.
+const ALWAYS_ALLOWED_WASI_IMPORTS: &[&str] = &["proc_exit", "proc_raise", "sched_yield"];
+
+/// Returns the WASI functions allowed by the `--allow` capability `capability`: names, or
+/// prefixes ending with `_`.
+fn wasi_capability_imports(capability: &str) -> &'static [&'static str] {
+    match capability {
+        "args" => &["args_"],
+        "env" => &["environ_"],
+        "clock" => &["clock_"],
+        "random" => &["random_get"],
+        "fs" => &["fd_", "path_"],
+        "poll" => &["poll_oneoff"],
+        "sock" => &["sock_"],
+        _ => &[],
+    }
+}
 
-        println!("Using libwasmer file: {}", libwasmer_path.display());
+/// C source generated from the command line: the glue code compiled with the Wasm object, or
+/// a header for it.
+#[derive(Debug, Default)]
+struct CGlue {
+    source: String,
+}
 
-        let lib_filename = libwasmer_path
-            .file_name()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string();
-        libwasmer_path.pop();
-        {
-            let mut c_src_file = fs::OpenOptions::new()
-                .create_new(true)
-                .write(true)
-                .open(&c_src_path)
-                .context("Failed to open C source code file")?;
-            c_src_file.write_all(WASMER_MAIN_C_SOURCE)?;
-        }
+impl CGlue {
+    /// Adds `#include {header}`, where `header` is `<name>` or `"name"`.
+    fn include(&mut self, header: &str) -> &mut Self {
+        self.source.push_str(&format!("#include {}\n", header));
+        self
+    }
 
-        if !header_path.is_dir() {
-            header_path.pop();
-        }
+    /// Adds `#define {name}`.
+    fn define(&mut self, name: &str) -> &mut Self {
+        self.source.push_str(&format!("#define {}\n", name));
+        self
+    }
 
-        /* Compile main function */
-        let compilation = {
-            let compiler_cmd = match Command::new("cc").output() {
-                Ok(_) => "cc",
-                Err(_) => "gcc",
-            };
+    /// Adds `#define {name} {value}`.
+    fn define_value(&mut self, name: &str, value: impl std::fmt::Display) -> &mut Self {
+        self.source
+            .push_str(&format!("#define {} {}\n", name, value));
+        self
+    }
 
-            Command::new(compiler_cmd)
-                .arg("-c")
-                .arg(&c_src_path)
-                .arg(if linkcode.optimization_flag.is_empty() {
-                    "-O2"
-                } else {
-                    linkcode.optimization_flag.as_str()
-                })
-                .arg(&format!("-L{}", libwasmer_path.display()))
-                .arg(&format!("-I{}", get_wasmer_include_directory()?.display()))
-                .arg(&format!("-l:{}", lib_filename))
-                //.arg("-lwasmer")
-                // Add libraries required per platform.
-                // We need userenv, sockets (Ws2_32), advapi32 for some system calls and bcrypt for random numbers.
-                //#[cfg(windows)]
-                //    .arg("-luserenv")
-                //    .arg("-lWs2_32")
-                //    .arg("-ladvapi32")
-                //    .arg("-lbcrypt")
-                // On unix we need dlopen-related symbols, libmath for a few things, and pthreads.
-                //#[cfg(not(windows))]
-                .arg("-ldl")
-                .arg("-lm")
-                .arg("-pthread")
-                .arg(&format!("-I{}", header_path.display()))
-                .arg("-v")
-                .arg("-o")
-                .arg("main_obj.obj")
-                .output()?
-        };
-        if !compilation.status.success() {
-            return Err(anyhow::anyhow!(String::from_utf8_lossy(
-                &compilation.stderr
-            )
-            .to_string()));
+    /// Adds the array `name` of the C string literals of `strings`, terminated by `NULL`.
+    fn string_array(&mut self, name: &str, strings: impl Iterator<Item = String>) -> &mut Self {
+        self.source.push_str(&format!(
+            "static const char *{}[] = {{{}NULL}};\n",
+            name,
+            c_string_list(strings)
+        ));
+        self
+    }
+
+    /// Opens an `extern "C"` block for C++ compilers, closed by [`CGlue::end_extern_c`].
+    fn begin_extern_c(&mut self) -> &mut Self {
+        self.raw("#ifdef __cplusplus\nextern \"C\" {\n#endif\n\n")
+    }
+
+    /// Closes the block opened by [`CGlue::begin_extern_c`].
+    fn end_extern_c(&mut self) -> &mut Self {
+        self.raw("#ifdef __cplusplus\n}\n#endif\n")
+    }
+
+    /// Adds `source` as it is.
+    fn raw(&mut self, source: &str) -> &mut Self {
+        self.source.push_str(source);
+        self
+    }
+
+    fn into_source(self) -> String {
+        self.source
+    }
+}
+
+/// Formats `string` as a C string literal.
+fn c_string_literal(string: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in string.bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                literal.push('\\');
+                literal.push(byte as char);
+            }
+            0x20..=0x7e => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{:03o}", byte)),
         }
-        linkcode.run().context("Failed to link objects together")?;
-        Ok(())
     }
+    literal.push('"');
+    literal
+}
+
+/// Formats `strings` as C string literals, each followed by a comma.
+fn c_string_list(strings: impl Iterator<Item = String>) -> String {
+    strings
+        .map(|string| format!("{}, ", c_string_literal(&string)))
+        .collect()
 }
 
 fn triple_to_zig_triple(target_triple: &Triple) -> String {
@@ -623,16 +2786,75 @@ fn triple_to_zig_triple(target_triple: &Triple) -> String {
     format!("{}-{}-{}", arch, os, env)
 }
 
+/// Returns the Wasmer installation holding the headers and `libwasmer`.
+///
+/// `WASMER_DIR` (or the compile-time `WASMER_INSTALL_PREFIX`) is used when set; otherwise
+/// `~/.wasmer`, `/usr/local` and the installation of the running `wasmer` binary are probed.
 fn get_wasmer_dir() -> anyhow::Result<PathBuf> {
-    Ok(PathBuf::from(
-        env::var("WASMER_DIR")
-            .or_else(|e| {
-                option_env!("WASMER_INSTALL_PREFIX")
-                    .map(str::to_string)
-                    .ok_or(e)
+    if let Some(dir) = env::var_os("WASMER_DIR")
+        .map(PathBuf::from)
+        .or_else(|| option_env!("WASMER_INSTALL_PREFIX").map(PathBuf::from))
+    {
+        return Ok(dir);
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(home) = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")) {
+        candidates.push(PathBuf::from(home).join(".wasmer"));
+    }
+    if cfg!(unix) {
+        candidates.push(PathBuf::from("/usr/local"));
+    }
+    // `<prefix>/bin/wasmer`
+    if let Some(prefix) = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.parent()?.to_path_buf()))
+    {
+        candidates.push(prefix);
+    }
+
+    if let Some(dir) = candidates.iter().find(|dir| is_wasmer_installation(dir)) {
+        return Ok(dir.clone());
+    }
+    bail!(
+        "Could not find a Wasmer installation with `include/wasmer.h` and `lib/libwasmer`; tried {}. Set `WASMER_DIR` to the directory where Wasmer is installed (usually `~/.wasmer`).",
+        candidates
+            .iter()
+            .map(|dir| format!("`{}`", dir.display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Whether `dir` holds the Wasmer headers and library.
+fn is_wasmer_installation(dir: &Path) -> bool {
+    let has_libwasmer = fs::read_dir(dir.join("lib"))
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok()).any(|entry| {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                name.starts_with("libwasmer.") || name.starts_with("wasmer.")
             })
-            .context("Trying to read env var `WASMER_DIR`")?,
-    ))
+        })
+        .unwrap_or(false);
+    has_libwasmer && dir.join("include").join("wasmer.h").is_file()
+}
+
+/// Returns the version in the `WASMER_VERSION` macro of the `wasmer.h` header `header`.
+fn wasmer_header_version(header: &str) -> Option<&str> {
+    header.lines().find_map(|line| {
+        let version = line.trim().strip_prefix("#define WASMER_VERSION ")?.trim();
+        version.strip_prefix('"')?.strip_suffix('"')
+    })
+}
+
+/// Returns the major and minor parts of the version `version`.
+fn major_minor(version: &str) -> (&str, &str) {
+    let mut parts = version.split(|c| c == '.' || c == '-');
+    (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    )
 }
 
 fn get_wasmer_include_directory() -> anyhow::Result<PathBuf> {
@@ -641,16 +2863,29 @@ fn get_wasmer_include_directory() -> anyhow::Result<PathBuf> {
     Ok(path)
 }
 
-/// path to the static libwasmer
-fn get_libwasmer_path() -> anyhow::Result<PathBuf> {
+/// path to the static or shared libwasmer, depending on `linkage`
+fn get_libwasmer_path(linkage: Linkage) -> anyhow::Result<PathBuf> {
     let mut path = get_wasmer_dir()?;
     path.push("lib");
 
     // TODO: prefer headless Wasmer if/when it's a separate library.
-    #[cfg(not(windows))]
-    path.push("libwasmer.a");
-    #[cfg(windows)]
-    path.push("wasmer.lib");
+    match linkage {
+        Linkage::Static => {
+            #[cfg(not(windows))]
+            path.push("libwasmer.a");
+            #[cfg(windows)]
+            path.push("wasmer.lib");
+        }
+        Linkage::Dynamic => {
+            // On Windows we link against the import library of the DLL.
+            #[cfg(windows)]
+            path.push("wasmer.dll.lib");
+            #[cfg(target_os = "macos")]
+            path.push("libwasmer.dylib");
+            #[cfg(all(not(windows), not(target_os = "macos")))]
+            path.push("libwasmer.so");
+        }
+    }
 
     Ok(path)
 }
@@ -663,12 +2898,299 @@ fn get_libwasmer_cache_path() -> anyhow::Result<PathBuf> {
     Ok(path)
 }
 
+/// Disassembles the object at `object_path` into `asm_path`, following the label of each
+/// function in `function_names` with its name in the Wasm module.
+#[cfg(feature = "static-artifact-create")]
+fn write_disassembly(
+    object_path: &Path,
+    function_names: &[(String, String)],
+    asm_path: &Path,
+) -> anyhow::Result<()> {
+    let (objdump, objdump_args) =
+        env_command("OBJDUMP")?.unwrap_or_else(|| (PathBuf::from("objdump"), vec![]));
+    let output = LinkError::run(
+        Command::new(objdump)
+            .args(objdump_args)
+            .arg("-d")
+            .arg(object_path),
+    )
+    .context("Failed to disassemble the compiled module")?;
+
+    let function_names = function_names
+        .iter()
+        .map(|(symbol, name)| (symbol.as_str(), name.as_str()))
+        .collect::<std::collections::HashMap<_, _>>();
+    let mut asm = String::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        asm.push_str(line);
+        // Labels look like `0000000000000000 <wasmer_function_0>:`, with a leading `_` on
+        // Mach-O.
+        let name = line
+            .strip_suffix(">:")
+            .and_then(|label| label.rsplit('<').next())
+            .and_then(|symbol| {
+                function_names
+                    .get(symbol)
+                    .or_else(|| function_names.get(symbol.strip_prefix('_')?))
+            });
+        if let Some(name) = name {
+            asm.push_str(&format!(" ; `{}`", name));
+        }
+        asm.push('\n');
+    }
+    fs::write(asm_path, asm).with_context(|| {
+        format!(
+            "Could not write the disassembly to `{}`",
+            asm_path.display()
+        )
+    })
+}
+
+/// Returns the words of the environment variable `name`, split like a shell would, or none if
+/// it isn't set.
+fn env_words(name: &str) -> anyhow::Result<Vec<String>> {
+    match env::var(name) {
+        Ok(value) => shell_words::split(&value)
+            .with_context(|| format!("Could not parse the `{}` environment variable", name)),
+        Err(_) => Ok(vec![]),
+    }
+}
+
+/// Returns the program named by the environment variable `name`, like `CC`, along with the
+/// arguments it must be run with first, or `None` if the variable is unset or empty.
+fn env_command(name: &str) -> anyhow::Result<Option<(PathBuf, Vec<String>)>> {
+    let mut words = env_words(name)?.into_iter();
+    Ok(words
+        .next()
+        .map(|program| (PathBuf::from(program), words.collect())))
+}
+
+/// The C compiler used when none was given with `--cc` or `WASMER_CC`.
+fn default_c_compiler() -> PathBuf {
+    if cfg!(windows) {
+        // We must use a C++ compiler on Windows because wasm.h uses `static_assert`
+        // which isn't available in `clang` on Windows.
+        PathBuf::from("clang++")
+    } else if Command::new("cc").output().is_ok() {
+        PathBuf::from("cc")
+    } else {
+        PathBuf::from("gcc")
+    }
+}
+
+/// Whether SSE2 is always enabled for `architecture` targets, because `compiler` requires it.
+fn requires_sse2(compiler: &CompilerType, architecture: &Architecture) -> bool {
+    *compiler == CompilerType::Cranelift
+        && matches!(architecture, Architecture::X86_64 | Architecture::X86_32(_))
+}
+
+/// Returns the features of the CPU named `cpu`, for targets of the given `architecture`.
+fn cpu_baseline(architecture: &Architecture, cpu: &str) -> anyhow::Result<Vec<CpuFeature>> {
+    use CpuFeature::*;
+    let x86_64_v1 = vec![SSE2];
+    let x86_64_v2 = [x86_64_v1.clone(), vec![SSE3, SSSE3, SSE41, SSE42, POPCNT]].concat();
+    let sandybridge = [x86_64_v2.clone(), vec![AVX]].concat();
+    let x86_64_v3 = [sandybridge.clone(), vec![AVX2, BMI1, BMI2, LZCNT]].concat();
+    let x86_64_v4 = [x86_64_v3.clone(), vec![AVX512F, AVX512DQ, AVX512VL]].concat();
+    let features = match architecture {
+        Architecture::X86_64 | Architecture::X86_32(_) => match cpu {
+            "x86-64" => Some(x86_64_v1),
+            "x86-64-v2" | "nehalem" | "westmere" => Some(x86_64_v2),
+            "sandybridge" | "ivybridge" => Some(sandybridge),
+            "x86-64-v3" | "haswell" | "broadwell" | "skylake" | "znver1" | "znver2" | "znver3" => {
+                Some(x86_64_v3)
+            }
+            "x86-64-v4" | "skylake-avx512" | "cascadelake" | "icelake-server" => Some(x86_64_v4),
+            _ => None,
+        },
+        // No aarch64 features can be enabled yet, the CPU name is only checked.
+        Architecture::Aarch64(_) => match cpu {
+            "generic" | "apple-m1" | "cortex-a72" | "neoverse-n1" => Some(vec![]),
+            _ => None,
+        },
+        _ => None,
+    };
+    features.ok_or_else(|| {
+        anyhow!(
+            "Unknown CPU `{}` for the {} architecture",
+            cpu,
+            architecture
+        )
+    })
+}
+
+/// Flags asking a linker of kind `linker_kind` to write the map of an executable for `target`
+/// to `map_path`.
+fn map_file_flags(
+    map_path: &Path,
+    linker_kind: LinkerKind,
+    target: &Triple,
+) -> anyhow::Result<Vec<String>> {
+    let map_path = map_path.display();
+    Ok(match (target.binary_format, linker_kind) {
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::CompilerDriver) => {
+            vec![format!("-Wl,-Map,{}", map_path)]
+        }
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::Linker) => {
+            vec!["-Map".to_string(), map_path.to_string()]
+        }
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::CompilerDriver) => {
+            vec![format!("-Wl,-map,{}", map_path)]
+        }
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::Linker) => {
+            vec!["-map".to_string(), map_path.to_string()]
+        }
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::CompilerDriver) => {
+            vec![format!("-Wl,/MAP:{}", map_path)]
+        }
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::Linker) => {
+            vec![format!("/MAP:{}", map_path)]
+        }
+        (binary_format, _) => bail!(
+            "Writing a linker map is not supported for {} executables.",
+            binary_format
+        ),
+    })
+}
+
+/// Arguments asking a linker of kind `linker_kind` to link every object of the archive at
+/// `archive_path` into a shared library for `target`, used or not.
+fn whole_archive_flags(
+    archive_path: &Path,
+    linker_kind: LinkerKind,
+    target: &Triple,
+) -> Vec<String> {
+    let archive_path = archive_path.display();
+    match (target.binary_format, linker_kind) {
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::CompilerDriver) => vec![
+            "-Wl,--whole-archive".to_string(),
+            archive_path.to_string(),
+            "-Wl,--no-whole-archive".to_string(),
+        ],
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::Linker) => vec![
+            "--whole-archive".to_string(),
+            archive_path.to_string(),
+            "--no-whole-archive".to_string(),
+        ],
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::CompilerDriver) => {
+            vec![format!("-Wl,-force_load,{}", archive_path)]
+        }
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::Linker) => {
+            vec!["-force_load".to_string(), archive_path.to_string()]
+        }
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::CompilerDriver) => {
+            vec![format!("-Wl,/WHOLEARCHIVE:{}", archive_path)]
+        }
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::Linker) => {
+            vec![format!("/WHOLEARCHIVE:{}", archive_path)]
+        }
+        _ => vec![archive_path.to_string()],
+    }
+}
+
+/// Flags asking the C compiler for debug information, if `debug` is set.
+fn debug_flags(debug: bool) -> &'static [&'static str] {
+    if debug {
+        &["-g"]
+    } else {
+        &[]
+    }
+}
+
+/// Flags turning the C compiler's warnings into errors, if `strict` is set.
+fn warning_flags(strict: bool) -> &'static [&'static str] {
+    if strict {
+        &["-Wall", "-Werror"]
+    } else {
+        &[]
+    }
+}
+
+/// Flags asking the C compiler for position-independent code, if `pie` is `Some(true)`.
+fn pic_flags(pie: Option<bool>) -> &'static [&'static str] {
+    match pie {
+        Some(true) => &["-fPIC"],
+        _ => &[],
+    }
+}
+
+/// Flags asking the C compiler to put each function and data object in its own section, so
+/// that the linker can drop the unused ones, if `gc_sections` is set.
+fn function_sections_flags(gc_sections: bool) -> &'static [&'static str] {
+    if gc_sections {
+        &["-ffunction-sections", "-fdata-sections"]
+    } else {
+        &[]
+    }
+}
+
+/// Flags asking a linker of kind `linker_kind` to drop unused sections from an executable for
+/// `target`, if `gc_sections` is set.
+fn gc_sections_flags(
+    gc_sections: bool,
+    linker_kind: LinkerKind,
+    target: &Triple,
+) -> &'static [&'static str] {
+    if !gc_sections {
+        return &[];
+    }
+    match (target.binary_format, linker_kind) {
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::CompilerDriver) => &["-Wl,--gc-sections"],
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::Linker) => &["--gc-sections"],
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::CompilerDriver) => &["-Wl,-dead_strip"],
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::Linker) => &["-dead_strip"],
+        // `link.exe` and `lld-link` already drop unreferenced functions in optimized builds.
+        _ => &[],
+    }
+}
+
+/// Flags asking the linker for a PIE (`Some(true)`) or a position-dependent executable
+/// (`Some(false)`).
+fn pie_flags(pie: Option<bool>) -> &'static [&'static str] {
+    match pie {
+        Some(true) => &["-pie"],
+        Some(false) => &["-no-pie"],
+        None => &[],
+    }
+}
+
+/// Flags asking a linker of kind `linker_kind` for a reproducible executable for `target`.
+fn reproducible_link_flags(linker_kind: LinkerKind, target: &Triple) -> &'static [&'static str] {
+    match (target.binary_format, linker_kind) {
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::CompilerDriver) => &["-Wl,--build-id=none"],
+        (wasmer_types::BinaryFormat::Elf, LinkerKind::Linker) => &["--build-id=none"],
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::CompilerDriver) => &["-Wl,/Brepro"],
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::Linker) => &["/Brepro"],
+        // ld64 derives the UUID of the executable from its contents.
+        _ => &[],
+    }
+}
+
+/// Flags asking a linker of kind `linker_kind` for a shared library for `target`.
+fn shared_library_flags(linker_kind: LinkerKind, target: &Triple) -> &'static [&'static str] {
+    match (target.binary_format, linker_kind) {
+        (_, LinkerKind::CompilerDriver) => &["-shared"],
+        (wasmer_types::BinaryFormat::Macho, LinkerKind::Linker) => &["-dylib"],
+        (wasmer_types::BinaryFormat::Coff, LinkerKind::Linker) => &["/DLL"],
+        (_, LinkerKind::Linker) => &["-shared"],
+    }
+}
+
 /// Compile the C code.
+#[allow(clippy::too_many_arguments)]
 fn run_c_compile(
+    c_compiler: &Path,
+    c_compiler_args: &[String],
     path_to_c_src: &Path,
     output_name: &Path,
     mut header_path: PathBuf,
-    target: Option<Triple>,
+    optimization_flag: &str,
+    debug: bool,
+    pie: Option<bool>,
+    gc_sections: bool,
+    strict: bool,
+    extra_args: &[String],
+    dry_run: bool,
 ) -> anyhow::Result<()> {
     debug_assert!(
         header_path.is_absolute(),
@@ -676,43 +3198,167 @@ fn run_c_compile(
         header_path.display()
     );
 
-    #[cfg(not(windows))]
-    let c_compiler = "cc";
-    // We must use a C++ compiler on Windows because wasm.h uses `static_assert`
-    // which isn't available in `clang` on Windows.
-    #[cfg(windows)]
-    let c_compiler = "clang++";
-
     if !header_path.is_dir() {
         header_path.pop();
     }
 
     let mut command = Command::new(c_compiler);
     let command = command
-        .arg("-O2")
+        .args(c_compiler_args)
+        .arg(optimization_flag)
+        .args(debug_flags(debug))
+        .args(pic_flags(pie))
+        .args(function_sections_flags(gc_sections))
+        .args(warning_flags(strict))
         .arg("-c")
         .arg(path_to_c_src)
         .arg(&format!("-I{}", header_path.display()))
-        .arg(&format!("-I{}", get_wasmer_include_directory()?.display()));
+        .arg(&format!("-I{}", get_wasmer_include_directory()?.display()))
+        .args(env_words("CFLAGS")?)
+        .args(extra_args);
 
-    let command = if let Some(target) = target {
-        command.arg("-target").arg(format!("{}", target))
-    } else {
-        command
-    };
+    run_command(command.arg("-o").arg(output_name), dry_run)?;
+    Ok(())
+}
 
-    let output = command.arg("-o").arg(output_name).output()?;
+/// Error from running the C compiler or the linker.
+#[derive(Debug)]
+enum LinkError {
+    /// The command could not be started.
+    Spawn {
+        /// The command line.
+        command: String,
+        /// The reason it could not be started.
+        source: std::io::Error,
+    },
+    /// The command ran but exited unsuccessfully.
+    Failed {
+        /// The command line.
+        command: String,
+        /// Exit status of the command.
+        status: std::process::ExitStatus,
+        /// Raw standard output of the command.
+        stdout: Vec<u8>,
+        /// Raw standard error of the command.
+        stderr: Vec<u8>,
+    },
+}
 
-    if !output.status.success() {
-        bail!(
-            "C code compile failed with: stdout: {}\n\nstderr: {}",
-            std::str::from_utf8(&output.stdout)
-                .expect("stdout is not utf8! need to handle arbitrary bytes"),
-            std::str::from_utf8(&output.stderr)
-                .expect("stderr is not utf8! need to handle arbitrary bytes")
-        );
+impl LinkError {
+    /// Runs `command` to completion, failing if it can't be started or exits unsuccessfully.
+    fn run(command: &mut Command) -> Result<std::process::Output, Self> {
+        let output = command.output().map_err(|source| Self::Spawn {
+            command: format!("{:?}", command),
+            source,
+        })?;
+        if !output.status.success() {
+            return Err(Self::Failed {
+                command: format!("{:?}", command),
+                status: output.status,
+                stdout: output.stdout,
+                stderr: output.stderr,
+            });
+        }
+        Ok(output)
     }
-    Ok(())
+}
+
+impl LinkError {
+    /// Returns the symbols the linker reported as undefined in its output, deduplicated and
+    /// in order of appearance.
+    fn undefined_symbols(&self) -> Vec<String> {
+        match self {
+            Self::Spawn { .. } => vec![],
+            Self::Failed { stdout, stderr, .. } => {
+                let mut output = String::from_utf8_lossy(stderr).into_owned();
+                // `link.exe` reports errors on its standard output.
+                output.push('\n');
+                output.push_str(&String::from_utf8_lossy(stdout));
+                parse_undefined_symbols(&output)
+            }
+        }
+    }
+}
+
+/// Parses the undefined symbols out of the output of GNU `ld`, `lld`, Apple's `ld64` and
+/// `link.exe`.
+fn parse_undefined_symbols(output: &str) -> Vec<String> {
+    let mut symbols: Vec<String> = vec![];
+    let mut in_ld64_list = false;
+    for line in output.lines() {
+        let symbol = if let Some((_, rest)) = line.split_once("undefined reference to ") {
+            // `foo' or 'foo', depending on the version of GNU `ld`.
+            rest.trim()
+                .trim_start_matches(|c| c == '`' || c == '\'')
+                .trim_end_matches('\'')
+        } else if let Some((_, rest)) = line.split_once("undefined symbol: ") {
+            rest.trim()
+        } else if let Some((_, rest)) = line.split_once("unresolved external symbol ") {
+            rest.split_whitespace().next().unwrap_or_default()
+        } else if line.starts_with("Undefined symbols for architecture") {
+            in_ld64_list = true;
+            continue;
+        } else if in_ld64_list {
+            // `  "_foo", referenced from:` followed by indented references.
+            match line.trim().strip_suffix("\", referenced from:") {
+                Some(symbol) => symbol.trim_start_matches('"'),
+                None if line.starts_with(' ') => continue,
+                None => {
+                    in_ld64_list = false;
+                    continue;
+                }
+            }
+        } else {
+            continue;
+        };
+        if !symbol.is_empty() && !symbols.iter().any(|known| known == symbol) {
+            symbols.push(symbol.to_string());
+        }
+    }
+    symbols
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spawn { command, source } => write!(f, "could not run {}: {}", command, source),
+            Self::Failed {
+                command,
+                status,
+                stdout,
+                stderr,
+            } => write!(
+                f,
+                "{} failed with {}: stdout: {}\n\nstderr: {}",
+                command,
+                status,
+                String::from_utf8_lossy(stdout),
+                String::from_utf8_lossy(stderr)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Spawn { source, .. } => Some(source),
+            Self::Failed { .. } => None,
+        }
+    }
+}
+
+/// Runs `command` like [`LinkError::run`], or only prints it and returns `None` if `dry_run`
+/// is set.
+fn run_command(
+    command: &mut Command,
+    dry_run: bool,
+) -> Result<Option<std::process::Output>, LinkError> {
+    if dry_run {
+        println!("{:?}", command);
+        return Ok(None);
+    }
+    LinkError::run(command).map(Some)
 }
 
 /// Data used to run a linking command for generated artifacts.
@@ -720,20 +3366,46 @@ fn run_c_compile(
 struct LinkCode {
     /// Path to the linker used to run the linking command.
     linker_path: PathBuf,
+    /// Arguments the linker is run with before any others.
+    linker_args: Vec<String>,
+    /// Whether `linker_path` is a compiler driver or a raw linker.
+    linker_kind: LinkerKind,
     /// String used as an optimization flag.
     optimization_flag: String,
+    /// Whether to emit debug information.
+    debug: bool,
+    /// Whether to link a PIE, see [`CreateExe::pie`].
+    pie: Option<bool>,
     /// Paths of objects to link.
     object_paths: Vec<PathBuf>,
-    /// Additional libraries to link against.
+    /// Additional libraries to link against, by name or as paths to existing files.
     additional_libraries: Vec<String>,
+    /// Additional directories to search for libraries.
+    library_paths: Vec<PathBuf>,
+    /// Whether to link against the system libraries libwasmer depends on.
+    default_libraries: bool,
+    /// Arguments the linker is run with after the default ones.
+    extra_args: Vec<String>,
+    /// Whether to drop unused sections from the executable.
+    gc_sections: bool,
+    /// Whether warnings in the C glue code fail the build.
+    strict_c: bool,
+    /// Where to write the linker map of the executable, if anywhere.
+    map_path: Option<PathBuf>,
+    /// Whether to link a shared library instead of an executable.
+    shared: bool,
+    /// Whether to ask the linker for a reproducible executable.
+    reproducible: bool,
     /// Path to the output target.
     output_path: PathBuf,
-    /// Path to the dir containing the static libwasmer library.
-    libwasmer_path: PathBuf,
-    /// The target to link the executable for.
-    target: Option<Triple>,
+    /// Whether libwasmer is linked statically or dynamically.
+    linkage: Linkage,
+    /// Verbosity level, as given by the number of `-v` flags.
+    verbose: u8,
     /// Working directory
     working_dir: PathBuf,
+    /// Whether to only print the linking command instead of running it.
+    dry_run: bool,
 }
 
 impl Default for LinkCode {
@@ -744,67 +3416,167 @@ impl Default for LinkCode {
         let linker = "clang";
         Self {
             linker_path: PathBuf::from(linker),
+            linker_args: vec![],
+            linker_kind: LinkerKind::default(),
             optimization_flag: String::from("-O2"),
+            debug: false,
+            pie: None,
             object_paths: vec![],
             additional_libraries: vec![],
+            library_paths: vec![],
+            default_libraries: true,
+            extra_args: vec![],
+            gc_sections: true,
+            strict_c: false,
+            map_path: None,
+            shared: false,
+            reproducible: false,
             output_path: PathBuf::from("a.out"),
-            libwasmer_path: get_libwasmer_path().unwrap(),
-            target: None,
+            linkage: Linkage::default(),
+            verbose: 0,
             working_dir: env::current_dir().expect("could not get current dir from environment"),
+            dry_run: false,
         }
     }
 }
 
 impl LinkCode {
     fn run(&self) -> anyhow::Result<()> {
-        let libwasmer_path = self
-            .libwasmer_path
+        let libwasmer_path = get_libwasmer_path(self.linkage)?
             .canonicalize()
             .context("Failed to find libwasmer")?;
-        println!(
+        verbose!(
+            self.verbose,
+            1,
             "Using path `{}` as libwasmer path.",
             libwasmer_path.display()
         );
+        // Cross-compiled executables are linked by `compile_zig` instead.
+        let target = Triple::host();
         let mut command = Command::new(&self.linker_path);
-        let command = command
-            .arg(&self.optimization_flag)
-            .args(
-                self.object_paths
-                    .iter()
-                    .map(|path| path.canonicalize().unwrap()),
-            )
-            .arg(&libwasmer_path);
-        let command = if let Some(target) = &self.target {
-            command.arg("-target").arg(format!("{}", target))
+        let command = command.args(&self.linker_args);
+        let command = if self.linker_kind == LinkerKind::CompilerDriver {
+            command
+                .arg(&self.optimization_flag)
+                .args(debug_flags(self.debug))
+        } else {
+            command
+        };
+        let command = if self.shared {
+            command.args(shared_library_flags(self.linker_kind, &target))
+        } else {
+            command.args(pie_flags(self.pie))
+        };
+        let gc_sections_flags = gc_sections_flags(self.gc_sections, self.linker_kind, &target);
+        if !gc_sections_flags.is_empty() {
+            verbose!(
+                self.verbose,
+                1,
+                "Dropping unused sections with {:?}",
+                gc_sections_flags
+            );
+        }
+        let command = command.args(gc_sections_flags);
+        let command = if self.reproducible {
+            if target.binary_format == wasmer_types::BinaryFormat::Macho {
+                // ld64 leaves the modification times of the objects out of the debug map.
+                command.env("ZERO_AR_DATE", "1");
+            }
+            command.args(reproducible_link_flags(self.linker_kind, &target))
         } else {
             command
         };
+        let command = match self.map_path.as_ref() {
+            Some(map_path) => command.args(map_file_flags(map_path, self.linker_kind, &target)?),
+            None => command,
+        };
+        let command = command.args(
+            self.object_paths
+                .iter()
+                // Objects don't exist in a dry run.
+                .map(|path| path.canonicalize().unwrap_or_else(|_| path.clone())),
+        );
+        // A shared library exports the whole Wasm C API of the libwasmer it embeds, so that its
+        // users create the engines and stores its modules are loaded in with it.
+        let command = if self.shared && self.linkage == Linkage::Static {
+            command.args(whole_archive_flags(
+                &libwasmer_path,
+                self.linker_kind,
+                &target,
+            ))
+        } else {
+            command.arg(&libwasmer_path)
+        };
+        // Let the executable find the shared libwasmer next to where it was linked from.
+        #[cfg(not(windows))]
+        let command = match (self.linkage, libwasmer_path.parent(), self.linker_kind) {
+            (Linkage::Dynamic, Some(libwasmer_dir), LinkerKind::CompilerDriver) => {
+                command.arg(format!("-Wl,-rpath,{}", libwasmer_dir.display()))
+            }
+            (Linkage::Dynamic, Some(libwasmer_dir), LinkerKind::Linker) => {
+                command.arg("-rpath").arg(libwasmer_dir)
+            }
+            _ => command,
+        };
+        let command = command.args(
+            self.library_paths
+                .iter()
+                .map(|dir| self.linker_kind.library_path_flag(dir)),
+        );
         // Add libraries required per platform.
-        // We need userenv, sockets (Ws2_32), advapi32 for some system calls and bcrypt for random numbers.
-        #[cfg(windows)]
+        let command = if self.default_libraries {
+            command.args(self.linker_kind.default_library_flags())
+        } else {
+            command
+        };
+        let link_against_extra_libs = self.additional_libraries.iter().map(|lib| {
+            // Paths to existing archives, such as vendored static libraries, are linked
+            // as they are instead of being looked up on the library search path.
+            if Path::new(lib).is_file() {
+                lib.clone()
+            } else {
+                self.linker_kind.library_flag(lib)
+            }
+        });
         let command = command
-            .arg("-luserenv")
-            .arg("-lWs2_32")
-            .arg("-ladvapi32")
-            .arg("-lbcrypt");
-        // On unix we need dlopen-related symbols, libmath for a few things, and pthreads.
-        #[cfg(not(windows))]
-        let command = command.arg("-ldl").arg("-lm").arg("-pthread");
-        let link_against_extra_libs = self
-            .additional_libraries
-            .iter()
-            .map(|lib| format!("-l{}", lib));
-        let command = command.args(link_against_extra_libs);
-        let output = command.arg("-o").arg(&self.output_path).output()?;
+            .args(link_against_extra_libs)
+            .args(env_words("LDFLAGS")?)
+            .args(&self.extra_args);
+        let command = if self.linker_kind == LinkerKind::Linker && cfg!(windows) {
+            // `lld-link` and `link.exe` follow the MSVC command line conventions.
+            command.arg(format!("/OUT:{}", self.output_path.display()))
+        } else {
+            command.arg("-o").arg(&self.output_path)
+        };
+        let output = match run_command(command, self.dry_run) {
+            Ok(output) => output,
+            Err(err) => {
+                let undefined_symbols = err.undefined_symbols();
+                if undefined_symbols.is_empty() {
+                    return Err(err.into());
+                }
+                return Err(anyhow::Error::new(err).context(format!(
+                    "Undefined symbols: {}\nIf they are defined by a library, link against it with `-l <library>`.",
+                    undefined_symbols.join(", ")
+                )));
+            }
+        };
+        if output.is_none() {
+            return Ok(());
+        }
 
-        if !output.status.success() {
-            bail!(
-                "linking failed with: stdout: {}\n\nstderr: {}",
-                std::str::from_utf8(&output.stdout)
-                    .expect("stdout is not utf8! need to handle arbitrary bytes"),
-                std::str::from_utf8(&output.stderr)
-                    .expect("stderr is not utf8! need to handle arbitrary bytes")
-            );
+        // Windows looks up DLLs next to the executable, so ship `wasmer.dll` along with it.
+        #[cfg(windows)]
+        if self.linkage == Linkage::Dynamic {
+            let dll_path = libwasmer_path.with_file_name("wasmer.dll");
+            if let Some(output_dir) = self.output_path.parent() {
+                fs::copy(&dll_path, output_dir.join("wasmer.dll")).with_context(|| {
+                    format!(
+                        "Could not copy `{}` next to the executable",
+                        dll_path.display()
+                    )
+                })?;
+            }
         }
         Ok(())
     }
@@ -1113,3 +3885,98 @@ fn find_zig_binary(path: Option<PathBuf>) -> Result<PathBuf> {
         Ok(retval)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_complete_serialized_module, major_minor, parse_undefined_symbols, wasmer_header_version,
+        CGlue, CreateExe,
+    };
+    use clap::Parser;
+    use wasmer_types::MetadataHeader;
+
+    #[test]
+    fn test_c_glue() {
+        let mut glue = CGlue::default();
+        glue.include("<stddef.h>")
+            .define("WASMER_RESTRICT_WASI")
+            .define_value("WASMER_MODULE_ALIGN", 16)
+            .string_array(
+                "names",
+                vec!["a\"b".to_string(), "é".to_string()].into_iter(),
+            );
+        assert_eq!(
+            glue.into_source(),
+            "#include <stddef.h>\n\
+             #define WASMER_RESTRICT_WASI\n\
+             #define WASMER_MODULE_ALIGN 16\n\
+             static const char *names[] = {\"a\\\"b\", \"\\303\\251\", NULL};\n"
+        );
+    }
+
+    #[test]
+    fn test_main_c_source_module_name() {
+        let create_exe = CreateExe::parse_from(&["create-exe", "module.wasm", "-o", "module.out"]);
+        let source = create_exe.main_c_source();
+        let (glue, main) = source
+            .split_once("#define WASMER_MODULE_NAME \"module\"\n")
+            .expect("the module name isn't defined");
+        assert!(!glue.contains("int main("), "{}", source);
+        assert!(main.contains("const char *module_name = WASMER_MODULE_NAME;"));
+    }
+
+    #[test]
+    fn test_wasmer_header_version() {
+        let header = "#define WASMER_H\n\n\
+                      // This file corresponds to the following Wasmer version.\n\
+                      #define WASMER_VERSION \"3.0.0-beta.2\"\n\
+                      #define WASMER_VERSION_MAJOR 3\n";
+        assert_eq!(wasmer_header_version(header), Some("3.0.0-beta.2"));
+        assert_eq!(
+            wasmer_header_version("#define WASMER_VERSION_MAJOR 3"),
+            None
+        );
+
+        assert_eq!(major_minor("3.0.0-beta.2"), ("3", "0"));
+        assert_eq!(major_minor("2.3.0"), ("2", "3"));
+    }
+
+    #[test]
+    fn test_parse_undefined_symbols() {
+        let gnu_ld = "/usr/bin/ld: wasm.o: in function `main':\n\
+                      wasmer_main.c:(.text+0x1c): undefined reference to `deflate'\n\
+                      /usr/bin/ld: wasmer_main.c:(.text+0x2a): undefined reference to `inflate'\n\
+                      /usr/bin/ld: wasmer_main.c:(.text+0x3f): undefined reference to `deflate'\n\
+                      collect2: error: ld returned 1 exit status";
+        assert_eq!(parse_undefined_symbols(gnu_ld), vec!["deflate", "inflate"]);
+
+        let lld = "ld.lld: error: undefined symbol: deflate\n\
+                   >>> referenced by wasmer_main.c\n\
+                   >>>               wasmer_main.o:(main)";
+        assert_eq!(parse_undefined_symbols(lld), vec!["deflate"]);
+
+        let ld64 = "Undefined symbols for architecture arm64:\n  \
+                    \"_deflate\", referenced from:\n      \
+                    _main in wasmer_main.o\n  \
+                    \"_inflate\", referenced from:\n      \
+                    _main in wasmer_main.o\n\
+                    ld: symbol(s) not found for architecture arm64";
+        assert_eq!(parse_undefined_symbols(ld64), vec!["_deflate", "_inflate"]);
+
+        let link_exe = "wasmer_main.obj : error LNK2019: unresolved external symbol deflate referenced in function main";
+        assert_eq!(parse_undefined_symbols(link_exe), vec!["deflate"]);
+
+        assert!(parse_undefined_symbols("ld: cannot find -lfoo").is_empty());
+    }
+
+    #[test]
+    fn test_is_complete_serialized_module() {
+        let mut bytes = wasmer_compiler::ArtifactBuild::MAGIC_HEADER.to_vec();
+        bytes.extend(MetadataHeader::new(4).into_bytes());
+        bytes.extend([1, 2, 3, 4]);
+        assert!(is_complete_serialized_module(&bytes));
+        assert!(!is_complete_serialized_module(&bytes[..bytes.len() - 1]));
+        assert!(!is_complete_serialized_module(&bytes[..20]));
+        assert!(!is_complete_serialized_module(b"\0asm\x01\0\0\0"));
+    }
+}
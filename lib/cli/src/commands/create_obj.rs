@@ -1,19 +1,22 @@
 //! Create a compiled standalone object file for a given Wasm file.
 
-use super::ObjectFormat;
-use crate::{commands::PrefixerFn, store::CompilerOptions};
-use anyhow::{Context, Result};
+use super::{compile_error_context, object_for_target, serialized_header, ObjectFormat};
+use crate::{
+    commands::PrefixerFn,
+    store::{CompilerOptions, CompilerType},
+    utils::parse_symbol_prefix,
+};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use std::env;
 use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufWriter;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use wasmer::*;
-use wasmer_object::{emit_serialized, get_object_for_target};
-
-const WASMER_SERIALIZED_HEADER: &[u8] = include_bytes!("wasmer_deserialize_module.h");
+use wasmer_object::{emit_serialized, Object};
+use wasmer_types::{entity::EntityRef, MetadataHeader, Symbol};
 
 #[derive(Debug, Parser)]
 /// The options for the `wasmer create-exe` subcommand
@@ -50,15 +53,23 @@ pub struct CreateObj {
 
     /// Object format options
     ///
-    /// This flag accepts two options: `symbols` or `serialized`.
+    /// This flag accepts three options: `symbols`, `serialized` or `llvm-ir`.
     /// - (default) `symbols` creates an object where all functions and metadata of the module are regular object symbols
     /// - `serialized` creates an object where the module is zero-copy serialized as raw data
+    /// - `llvm-ir` writes the LLVM IR of the module to the output path with a `.ll` extension, and requires `--llvm`
     #[clap(name = "OBJECT_FORMAT", long = "object-format", verbatim_doc_comment)]
     object_format: Option<ObjectFormat>,
 
     #[clap(short = 'm', multiple = true, number_of_values = 1)]
     cpu_features: Vec<CpuFeature>,
 
+    /// Prefix for the names of the symbols in the object file
+    ///
+    /// Objects generated with different prefixes can be linked into the same executable. With
+    /// the `symbols` format, the prefix defaults to a hash of the Wasm module.
+    #[clap(long = "symbol-prefix", parse(try_from_str = parse_symbol_prefix))]
+    symbol_prefix: Option<String>,
+
     #[clap(flatten)]
     compiler: CompilerOptions,
 }
@@ -103,45 +114,59 @@ impl CreateObj {
         let wasm_module_path = starting_cd.join(&self.path);
 
         match object_format {
+            ObjectFormat::LlvmIr => {
+                if compiler_type != CompilerType::LLVM {
+                    bail!("`--object-format llvm-ir` requires the LLVM compiler (`--llvm`).");
+                }
+                let ir_output = self.output.with_extension("ll");
+                #[cfg(feature = "llvm")]
+                write_llvm_ir(
+                    &self.compiler,
+                    &target,
+                    &fs::read(&wasm_module_path)?,
+                    &starting_cd.join(&ir_output),
+                )?;
+                eprintln!("✔ LLVM IR written to `{}`.", ir_output.display());
+                return Ok(());
+            }
             ObjectFormat::Serialized => {
-                let module = Module::from_file(&store, &wasm_module_path)
-                    .context("failed to compile Wasm")?;
+                let wasm_bytes = fs::read(&wasm_module_path)
+                    .with_context(|| format!("Could not read `{}`", wasm_module_path.display()))?;
+                let module = Module::new(&store, &wasm_bytes).map_err(|err| {
+                    compile_error_context(
+                        err,
+                        &wasm_module_path,
+                        &compiler_type.to_string(),
+                        &target,
+                    )
+                })?;
                 let bytes = module.serialize()?;
-                let mut obj = get_object_for_target(target.triple())?;
-                emit_serialized(&mut obj, &bytes, target.triple())?;
+                let mut obj = object_for_target(target.triple())?;
+                emit_serialized(
+                    &mut obj,
+                    &bytes,
+                    target.triple(),
+                    &format!(
+                        "{}WASMER_MODULE",
+                        self.symbol_prefix.as_deref().unwrap_or_default()
+                    ),
+                    MetadataHeader::ALIGN as u64,
+                )?;
                 let mut writer = BufWriter::new(File::create(&output_path)?);
                 obj.write_stream(&mut writer)
                     .map_err(|err| anyhow::anyhow!(err.to_string()))?;
                 writer.flush()?;
                 let mut writer = BufWriter::new(File::create(&header_output_path)?);
-                writer.write_all(WASMER_SERIALIZED_HEADER)?;
+                writer.write_all(
+                    serialized_header(self.symbol_prefix.as_deref(), false).as_bytes(),
+                )?;
                 writer.flush()?;
             }
             ObjectFormat::Symbols => {
-                let engine = store.engine();
-                let engine_inner = engine.inner();
-                let compiler = engine_inner.compiler()?;
-                let features = engine_inner.features();
-                let tunables = store.tunables();
                 let data: Vec<u8> = fs::read(wasm_module_path)?;
-                let prefixer: Option<PrefixerFn> = None;
-                let (module_info, obj, metadata_length, symbol_registry) =
-                    Artifact::generate_object(
-                        compiler, &data, prefixer, &target, tunables, features,
-                    )?;
-
-                let header_file_src = crate::c_gen::staticlib_header::generate_header_file(
-                    &module_info,
-                    &*symbol_registry,
-                    metadata_length,
-                );
-                let mut writer = BufWriter::new(File::create(&output_path)?);
-                obj.write_stream(&mut writer)
-                    .map_err(|err| anyhow::anyhow!(err.to_string()))?;
-                writer.flush()?;
-                let mut writer = BufWriter::new(File::create(&header_output_path)?);
-                writer.write_all(header_file_src.as_bytes())?;
-                writer.flush()?;
+                let symbols_object =
+                    generate_symbols_object(&store, &target, &data, self.symbol_prefix.as_deref())?;
+                write_symbols_object(&symbols_object, &output_path, &header_output_path)?;
             }
         }
 
@@ -164,3 +189,138 @@ impl CreateObj {
         Ok(())
     }
 }
+
+/// Compiles `wasm_bytes` with LLVM, and writes the IR of all its functions and trampolines,
+/// linked into a single module, to `ir_path`.
+#[cfg(feature = "llvm")]
+fn write_llvm_ir(
+    compiler: &CompilerOptions,
+    target: &Target,
+    wasm_bytes: &[u8],
+    ir_path: &Path,
+) -> Result<()> {
+    use std::sync::{Arc, Mutex};
+    use wasmer_compiler_llvm::{CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks};
+
+    /// Collects the bitcode of each module LLVM optimized.
+    #[derive(Debug, Default)]
+    struct BitcodeCollector {
+        bitcodes: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl LLVMCallbacks for BitcodeCollector {
+        fn preopt_ir(&self, _kind: &CompiledKind, _module: &InkwellModule) {}
+
+        fn postopt_ir(&self, _kind: &CompiledKind, module: &InkwellModule) {
+            let bitcode = module.write_bitcode_to_memory().as_slice().to_vec();
+            self.bitcodes.lock().unwrap().push(bitcode);
+        }
+
+        fn obj_memory_buffer(&self, _kind: &CompiledKind, _memory_buffer: &InkwellMemoryBuffer) {}
+    }
+
+    let collector = Arc::new(BitcodeCollector::default());
+    let mut compiler = compiler.clone();
+    compiler.set_llvm_callbacks(collector.clone());
+    let (store, _) = compiler.get_store_for_target(target.clone())?;
+    Module::new(&store, wasm_bytes).context("failed to compile Wasm")?;
+
+    let ir = wasmer_compiler_llvm::link_bitcode_to_ir(&collector.bitcodes.lock().unwrap())?;
+    fs::write(ir_path, ir)
+        .with_context(|| format!("Could not write LLVM IR to `{}`", ir_path.display()))?;
+    Ok(())
+}
+
+/// An object in the `symbols` format, from [`generate_symbols_object`].
+pub(crate) struct SymbolsObject<'a> {
+    /// The object itself.
+    pub(crate) object: Object<'a>,
+    /// Source of the header that goes with the object.
+    pub(crate) header: String,
+    /// Symbols of the functions defined by the module, along with their names in the module.
+    pub(crate) function_names: Vec<(String, String)>,
+}
+
+/// Compiles `wasm_bytes` to an object in the `symbols` format, and generates the header that
+/// goes with it.
+///
+/// The `symbol_prefix` is passed to the compiler as the prefix of the metadata, function and
+/// trampoline symbols. It defaults to a hash of `wasm_bytes`, so that the objects of different
+/// modules can be linked together.
+///
+/// This pair is what `create-exe --header` expects: the object defines `WASMER_METADATA` and
+/// every function and trampoline symbol the header declares `extern`, and the header defines
+/// `module_bytes_len` and `wasmer_object_module_new` on top of them.
+pub(crate) fn generate_symbols_object<'a>(
+    store: &Store,
+    target: &'a Target,
+    wasm_bytes: &[u8],
+    symbol_prefix: Option<&str>,
+) -> Result<SymbolsObject<'a>> {
+    let engine = store.engine();
+    let engine_inner = engine.inner();
+    let compiler = engine_inner.compiler()?;
+    let features = engine_inner.features();
+    let tunables = store.tunables();
+    let prefixer: PrefixerFn = match symbol_prefix {
+        Some(prefix) => {
+            let prefix = prefix.to_string();
+            Box::new(move |_: &[u8]| prefix.clone())
+        }
+        None => Box::new(|wasm_bytes: &[u8]| blake3::hash(wasm_bytes).to_hex()[..16].to_string()),
+    };
+    let (module_info, obj, metadata_length, symbol_registry) = Artifact::generate_object(
+        compiler,
+        wasm_bytes,
+        Some(prefixer),
+        target,
+        tunables,
+        features,
+    )?;
+
+    let header = crate::c_gen::staticlib_header::generate_header_file(
+        &module_info,
+        &*symbol_registry,
+        metadata_length,
+    );
+    let function_names = module_info
+        .functions
+        .keys()
+        .filter_map(|function_index| {
+            let local_index = module_info.local_func_index(function_index)?;
+            let name = module_info
+                .function_names
+                .get(&function_index)
+                .cloned()
+                .unwrap_or_else(|| format!("function {}", function_index.index()));
+            Some((
+                symbol_registry.symbol_to_name(Symbol::LocalFunction(local_index)),
+                name,
+            ))
+        })
+        .collect();
+    Ok(SymbolsObject {
+        object: obj,
+        header,
+        function_names,
+    })
+}
+
+/// Writes an object and its header from [`generate_symbols_object`] to `object_path` and
+/// `header_path`.
+pub(crate) fn write_symbols_object(
+    symbols_object: &SymbolsObject,
+    object_path: &Path,
+    header_path: &Path,
+) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(object_path)?);
+    symbols_object
+        .object
+        .write_stream(&mut writer)
+        .map_err(|err| anyhow::anyhow!(err.to_string()))?;
+    writer.flush()?;
+    let mut writer = BufWriter::new(File::create(header_path)?);
+    writer.write_all(symbols_object.header.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
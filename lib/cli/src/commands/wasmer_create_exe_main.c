@@ -44,10 +44,60 @@ static void pass_mapdir_arg(wasi_config_t *wasi_config, char *mapdir) {
   free(dir);
 }
 
-// We try to parse out `--dir` and `--mapdir` ahead of time and process those
-// specially. All other arguments are passed to the guest program.
+static void pass_env_arg(wasi_config_t *wasi_config, char *env) {
+  char *equal = strchr(env, '=');
+  if (!equal || equal == env) {
+    // error malformed argument
+    fprintf(stderr, "Expected env argument of the form KEY=VALUE\n");
+    exit(-1);
+  }
+
+  int key_len = equal - env;
+  char *key = (char *)malloc(key_len + 1);
+  memcpy(key, env, key_len);
+  key[key_len] = '\0';
+
+  wasi_config_env(wasi_config, key, equal + 1);
+  free(key);
+}
+
+// Whether the environment variable `key` is set with `--env` on the command line.
+static bool env_arg_given(int argc, char *argv[], const char *key) {
+  size_t key_len = strlen(key);
+  for (int i = 1; i < argc; ++i) {
+    const char *env = NULL;
+    if (strcmp(argv[i], "--env") == 0 && (i + 1) < argc) {
+      env = argv[++i];
+    } else if (strncmp(argv[i], "--env=", strlen("--env=")) == 0) {
+      env = argv[i] + strlen("--env=");
+    }
+    if (env && strncmp(env, key, key_len) == 0 && env[key_len] == '=') {
+      return true;
+    }
+  }
+  return false;
+}
+
+// We try to parse out `--dir`, `--mapdir` and `--env` ahead of time and process
+// those specially. All other arguments are passed to the guest program.
+//
+// The defaults given to `wasmer create-exe` come first: the directories are
+// always mapped, the environment variables are set unless they are given with
+// `--env`, and the arguments are used if no guest argument is given.
 static void handle_arguments(wasi_config_t *wasi_config, int argc,
                              char *argv[]) {
+  for (int i = 0; wasmer_default_mapdirs[i]; i += 2) {
+    wasi_config_mapdir(wasi_config, wasmer_default_mapdirs[i],
+                       wasmer_default_mapdirs[i + 1]);
+  }
+  for (int i = 0; wasmer_default_env[i]; i += 2) {
+    if (!env_arg_given(argc, argv, wasmer_default_env[i])) {
+      wasi_config_env(wasi_config, wasmer_default_env[i],
+                      wasmer_default_env[i + 1]);
+    }
+  }
+
+  int guest_argc = 0;
   for (int i = 1; i < argc; ++i) {
     // We probably want special args like `--dir` and `--mapdir` to not be
     // passed directly
@@ -80,12 +130,86 @@ static void handle_arguments(wasi_config_t *wasi_config, int argc,
       // this arg is a mapdir
       char *mapdir = argv[i] + strlen("--mapdir=");
       pass_mapdir_arg(wasi_config, mapdir);
+    } else if (strcmp(argv[i], "--env") == 0) {
+      // next arg is an environment variable
+      if ((i + 1) < argc) {
+        i++;
+        pass_env_arg(wasi_config, argv[i]);
+      } else {
+        fprintf(stderr, "--env expects a following argument of the form "
+                        "KEY=VALUE\n");
+        exit(-1);
+      }
+    } else if (strncmp(argv[i], "--env=", strlen("--env=")) == 0) {
+      // this arg is an environment variable
+      char *env = argv[i] + strlen("--env=");
+      pass_env_arg(wasi_config, env);
     } else {
       // guest argument
       wasi_config_arg(wasi_config, argv[i]);
+      guest_argc++;
     }
   }
+
+  if (guest_argc == 0) {
+    for (int i = 0; wasmer_default_args[i]; ++i) {
+      wasi_config_arg(wasi_config, wasmer_default_args[i]);
+    }
+  }
+}
+
+#ifdef WASMER_RESTRICT_WASI
+// The WASI errno returned by the functions denied with `--allow`.
+#define WASMER_WASI_ENOSYS 52
+
+static wasm_trap_t *denied_wasi_import(const wasm_val_vec_t *args,
+                                       wasm_val_vec_t *results) {
+  (void)args;
+  if (results->size > 0) {
+    results->data[0].kind = WASM_I32;
+    results->data[0].of.i32 = WASMER_WASI_ENOSYS;
+  }
+  return NULL;
+}
+
+// Whether the WASI function `name` of `length` bytes is listed in
+// `wasmer_allowed_wasi_imports`, whose entries ending with `_` are prefixes.
+static bool wasi_import_allowed(const char *name, size_t length) {
+  for (int i = 0; wasmer_allowed_wasi_imports[i]; ++i) {
+    const char *allowed = wasmer_allowed_wasi_imports[i];
+    size_t allowed_length = strlen(allowed);
+    bool prefix = allowed[allowed_length - 1] == '_';
+    if ((prefix ? length >= allowed_length : length == allowed_length) &&
+        strncmp(name, allowed, allowed_length) == 0) {
+      return true;
+    }
+  }
+  return false;
 }
+
+// Replaces the WASI functions imported by `module` that aren't allowed with
+// functions returning `ENOSYS`.
+static void deny_wasi_imports(wasm_store_t *store, wasm_module_t *module,
+                              wasm_extern_vec_t *imports) {
+  wasm_importtype_vec_t import_types;
+  wasm_module_imports(module, &import_types);
+  for (size_t i = 0; i < import_types.size; ++i) {
+    const wasm_name_t *module_name = wasm_importtype_module(import_types.data[i]);
+    const wasm_name_t *name = wasm_importtype_name(import_types.data[i]);
+    const wasm_functype_t *functype = wasm_externtype_as_functype_const(
+        wasm_importtype_type(import_types.data[i]));
+    if (!functype || module_name->size < strlen("wasi_") ||
+        strncmp(module_name->data, "wasi_", strlen("wasi_")) != 0 ||
+        wasi_import_allowed(name->data, name->size)) {
+      continue;
+    }
+    wasm_func_t *denied = wasm_func_new(store, functype, denied_wasi_import);
+    wasm_extern_delete(imports->data[i]);
+    imports->data[i] = wasm_func_as_extern(denied);
+  }
+  wasm_importtype_vec_delete(&import_types);
+}
+#endif
 #endif
 
 int main(int argc, char *argv[]) {
@@ -93,10 +217,26 @@ int main(int argc, char *argv[]) {
   wasm_engine_t *engine = wasm_engine_new_with_config(config);
   wasm_store_t *store = wasm_store_new(engine);
 
-  wasm_module_t *module = wasmer_object_module_new(store, "module");
+#ifdef WASMER_MODULE_DISPATCH
+  if (argc < 2) {
+    fprintf(stderr, "Usage: %s <module> [args...]\n\nAvailable modules:\n", argv[0]);
+    for (const char **name = wasmer_module_names; *name; ++name) {
+      fprintf(stderr, "  %s\n", *name);
+    }
+    return -1;
+  }
+  // The first argument selects the module; the remaining ones are its own.
+  const char *module_name = argv[1];
+  argc--;
+  argv++;
+#else
+  const char *module_name = WASMER_MODULE_NAME;
+#endif
+
+  wasm_module_t *module = wasmer_object_module_new(store, module_name);
 
   if (!module) {
-    fprintf(stderr, "Failed to create module\n");
+    fprintf(stderr, "Failed to create module `%s`\n", module_name);
     print_wasmer_error();
     return -1;
   }
@@ -132,6 +272,9 @@ int main(int argc, char *argv[]) {
 
     return 1;
   }
+#ifdef WASMER_RESTRICT_WASI
+  deny_wasi_imports(store, module, &imports);
+#endif
 #endif
 
   wasm_instance_t *instance = wasm_instance_new(store, module, &imports, NULL);
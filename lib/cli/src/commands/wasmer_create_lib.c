@@ -0,0 +1,32 @@
+#include "wasmer.h"
+#include "static_defs.h"
+
+extern wasm_module_t* wasmer_object_module_new(wasm_store_t* store, const char* module_name) asm("wasmer_object_module_new");
+
+#ifdef _WIN32
+#define WASMER_LIB_EXPORT __declspec(dllexport)
+#else
+#define WASMER_LIB_EXPORT __attribute__((visibility("default")))
+#endif
+
+// Creates the module `module_name` embedded in this library in `store`, or
+// returns NULL. A library built from a single Wasm file embeds it as `module`;
+// one built from several Wasm files names each module after its file stem.
+WASMER_LIB_EXPORT wasm_module_t *wasmer_module_new(wasm_store_t *store,
+                                                   const char *module_name) {
+  return wasmer_object_module_new(store, module_name);
+}
+
+// Creates the module `module_name` embedded in this library in `store` and
+// instantiates it with `imports`, as `wasm_instance_new` does, or returns NULL.
+WASMER_LIB_EXPORT wasm_instance_t *
+wasmer_module_instantiate(wasm_store_t *store, const char *module_name,
+                          const wasm_extern_vec_t *imports, wasm_trap_t **trap) {
+  wasm_module_t *module = wasmer_object_module_new(store, module_name);
+  if (!module) {
+    return NULL;
+  }
+  wasm_instance_t *instance = wasm_instance_new(store, module, imports, trap);
+  wasm_module_delete(module);
+  return instance;
+}
@@ -0,0 +1,43 @@
+#include "wasmer.h"
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <zstd.h>
+
+// The serialized modules are compressed, as with `--compress-modules`.
+#define WASMER_COMPRESSED_MODULES
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Decompresses the `length` bytes of zstd-compressed `data` into a buffer aligned to `align`,
+// and deserializes the `original_length` bytes of serialized module it holds.
+static wasm_module_t* wasmer_module_deserialize_compressed(wasm_store_t* store, const char* data, size_t length, size_t original_length, size_t align) {
+  char* buffer = (char*)malloc(original_length + align);
+  if (!buffer) {
+    fprintf(stderr, "Could not allocate %zu bytes to decompress the module\n", original_length);
+    return NULL;
+  }
+  char* aligned = (char*)(((uintptr_t)buffer + align - 1) & ~(uintptr_t)(align - 1));
+  size_t decompressed_length = ZSTD_decompress(aligned, original_length, data, length);
+  if (ZSTD_isError(decompressed_length) || decompressed_length != original_length) {
+    fprintf(stderr, "Could not decompress the module: %s\n",
+            ZSTD_isError(decompressed_length) ? ZSTD_getErrorName(decompressed_length) : "unexpected length");
+    free(buffer);
+    return NULL;
+  }
+
+  wasm_byte_vec_t module_byte_vec = {
+    .size = original_length,
+    .data = aligned,
+  };
+  wasm_module_t* module = wasm_module_deserialize(store, &module_byte_vec);
+  free(buffer);
+
+  return module;
+}
+
+#ifdef __cplusplus
+}
+#endif
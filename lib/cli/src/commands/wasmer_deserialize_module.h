@@ -1,4 +1,6 @@
 #include "wasmer.h"
+#include <assert.h>
+#include <stdint.h>
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
@@ -7,10 +9,31 @@
 extern "C" {
 #endif
 
-extern size_t WASMER_MODULE_LENGTH asm("WASMER_MODULE_LENGTH");
-extern char WASMER_MODULE_DATA asm("WASMER_MODULE_DATA");
+// Prefix of the symbols in the object, as given with `--symbol-prefix`.
+#ifndef WASMER_SYMBOL_PREFIX
+#define WASMER_SYMBOL_PREFIX
+#endif
+#define WASMER_STRINGIFY_(x) #x
+#define WASMER_STRINGIFY(x) WASMER_STRINGIFY_(x)
+
+extern size_t WASMER_MODULE_LENGTH asm(WASMER_STRINGIFY(WASMER_SYMBOL_PREFIX) "WASMER_MODULE_LENGTH");
+extern char WASMER_MODULE_DATA asm(WASMER_STRINGIFY(WASMER_SYMBOL_PREFIX) "WASMER_MODULE_DATA");
+#ifdef WASMER_COMPRESSED_MODULES
+extern size_t WASMER_MODULE_ORIGINAL_LENGTH asm(WASMER_STRINGIFY(WASMER_SYMBOL_PREFIX) "WASMER_MODULE_ORIGINAL_LENGTH");
+#endif
+
+// Alignment of the serialized module, `MetadataHeader::ALIGN` on the Rust side, which
+// create-exe defines before including this header.
+#ifndef WASMER_MODULE_ALIGN
+#define WASMER_MODULE_ALIGN 16
+#endif
 
 wasm_module_t* wasmer_object_module_new(wasm_store_t* store, const char* module_name) {
+#ifdef WASMER_COMPRESSED_MODULES
+  return wasmer_module_deserialize_compressed(store, &WASMER_MODULE_DATA, WASMER_MODULE_LENGTH,
+                                              WASMER_MODULE_ORIGINAL_LENGTH, WASMER_MODULE_ALIGN);
+#else
+  assert((uintptr_t)&WASMER_MODULE_DATA % WASMER_MODULE_ALIGN == 0);
   wasm_byte_vec_t module_byte_vec = {
     .size = WASMER_MODULE_LENGTH,
     .data = (const char*)&WASMER_MODULE_DATA,
@@ -18,6 +41,7 @@ wasm_module_t* wasmer_object_module_new(wasm_store_t* store, const char* module_
   wasm_module_t* module = wasm_module_deserialize(store, &module_byte_vec);
 
   return module;
+#endif
 }
 
 #ifdef __cplusplus
@@ -50,13 +50,28 @@ pub struct CompilerOptions {
     #[clap(long, parse(from_os_str))]
     llvm_debug_dir: Option<PathBuf>,
 
+    /// Callbacks given to LLVM instead of the ones writing to `llvm_debug_dir`.
+    #[cfg(feature = "llvm")]
+    #[clap(skip)]
+    llvm_callbacks: Option<Arc<dyn wasmer_compiler_llvm::LLVMCallbacks>>,
+
     #[clap(flatten)]
     features: WasmFeatures,
 }
 
 #[cfg(feature = "compiler")]
 impl CompilerOptions {
-    fn get_compiler(&self) -> Result<CompilerType> {
+    /// Makes LLVM report the code it generates to `callbacks`, instead of writing it to the
+    /// `--llvm-debug-dir`.
+    #[cfg(all(feature = "llvm", feature = "static-artifact-create"))]
+    pub(crate) fn set_llvm_callbacks(
+        &mut self,
+        callbacks: Arc<dyn wasmer_compiler_llvm::LLVMCallbacks>,
+    ) {
+        self.llvm_callbacks = Some(callbacks);
+    }
+
+    pub(crate) fn get_compiler(&self) -> Result<CompilerType> {
         if self.cranelift {
             Ok(CompilerType::Cranelift)
         } else if self.llvm {
@@ -241,7 +256,9 @@ impl CompilerOptions {
                     }
                 }
 
-                if let Some(ref llvm_debug_dir) = self.llvm_debug_dir {
+                if let Some(callbacks) = self.llvm_callbacks.as_ref() {
+                    config.callbacks(Some(callbacks.clone()));
+                } else if let Some(ref llvm_debug_dir) = self.llvm_debug_dir {
                     config.callbacks(Some(Arc::new(Callbacks::new(llvm_debug_dir.clone())?)));
                 }
                 if self.enable_verifier {
@@ -64,9 +64,27 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses a prefix for the symbols of generated object files, which must be usable as the
+/// start of a C identifier.
+pub fn parse_symbol_prefix(entry: &str) -> Result<String> {
+    if entry.is_empty() {
+        bail!("Symbol prefix must not be empty");
+    }
+    if entry.starts_with(|c: char| c.is_ascii_digit()) {
+        bail!("Symbol prefix must not start with a digit; got `{}`", entry);
+    }
+    if !entry.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!(
+            "Symbol prefix may only contain ASCII letters, digits and underscores; got `{}`",
+            entry
+        );
+    }
+    Ok(entry.to_string())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{parse_envvar, parse_symbol_prefix};
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +107,13 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_symbol_prefix() {
+        assert_eq!(parse_symbol_prefix("my_lib_").unwrap(), "my_lib_");
+        assert_eq!(parse_symbol_prefix("_0").unwrap(), "_0");
+        assert!(parse_symbol_prefix("").is_err());
+        assert!(parse_symbol_prefix("0lib").is_err());
+        assert!(parse_symbol_prefix("my-lib").is_err());
+    }
 }
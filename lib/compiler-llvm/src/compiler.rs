@@ -47,10 +47,14 @@ impl SymbolRegistry for ShortNames {
             Symbol::Section(index) => format!("s{}", index.index()),
             Symbol::FunctionCallTrampoline(index) => format!("t{}", index.index()),
             Symbol::DynamicFunctionTrampoline(index) => format!("d{}", index.index()),
+            Symbol::Metadata => "WASMER_METADATA".to_string(),
         }
     }
 
     fn name_to_symbol(&self, name: &str) -> Option<Symbol> {
+        if name == "WASMER_METADATA" {
+            return Some(Symbol::Metadata);
+        }
         if name.len() < 2 {
             return None;
         }
@@ -70,6 +74,23 @@ impl SymbolRegistry for ShortNames {
     }
 }
 
+/// Links the bitcode of modules compiled separately, such as the ones given to
+/// [`LLVMCallbacks::postopt_ir`](crate::LLVMCallbacks::postopt_ir), into a single module,
+/// and returns its textual IR.
+pub fn link_bitcode_to_ir(bitcodes: &[Vec<u8>]) -> Result<String, CompileError> {
+    let ctx = Context::create();
+    let merged_module = ctx.create_module("");
+    for bitcode in bitcodes {
+        let membuf = MemoryBuffer::create_from_memory_range(bitcode, "");
+        let module = Module::parse_bitcode_from_buffer(&membuf, &ctx)
+            .map_err(|err| CompileError::Codegen(err.to_string()))?;
+        merged_module
+            .link_in_module(module)
+            .map_err(|err| CompileError::Codegen(err.to_string()))?;
+    }
+    Ok(merged_module.print_to_string().to_string())
+}
+
 impl LLVMCompiler {
     fn compile_native_object<'data, 'module>(
         &self,
@@ -164,8 +185,11 @@ impl LLVMCompiler {
                 .collect::<Vec<_>>()
                 .as_slice(),
         );
-        let metadata_gv =
-            merged_module.add_global(metadata_init.get_type(), None, "WASMER_METADATA");
+        let metadata_gv = merged_module.add_global(
+            metadata_init.get_type(),
+            None,
+            &symbol_registry.symbol_to_name(Symbol::Metadata),
+        );
         metadata_gv.set_initializer(&metadata_init);
         metadata_gv.set_linkage(Linkage::DLLExport);
         metadata_gv.set_dll_storage_class(DLLStorageClass::Export);
@@ -20,7 +20,7 @@ mod object_file;
 mod trampoline;
 mod translator;
 
-pub use crate::compiler::LLVMCompiler;
+pub use crate::compiler::{link_bitcode_to_ir, LLVMCompiler};
 pub use crate::config::{
     CompiledKind, InkwellMemoryBuffer, InkwellModule, LLVMCallbacks, LLVMOptLevel, LLVM,
 };
@@ -32,7 +32,7 @@ use wasmer_types::{
     SignatureIndex, TableIndex,
 };
 #[cfg(feature = "static-artifact-create")]
-use wasmer_types::{CompileModuleInfo, Target};
+use wasmer_types::{CompileModuleInfo, Symbol, SymbolRegistry, Target};
 use wasmer_vm::{FunctionBodyPtr, MemoryStyle, TableStyle, VMSharedSignatureIndex, VMTrampoline};
 use wasmer_vm::{InstanceAllocator, InstanceHandle, StoreObjects, TrapHandlerFn, VMExtern};
 
@@ -51,9 +51,6 @@ pub struct Artifact {
 #[cfg(feature = "static-artifact-create")]
 pub type PrefixerFn = Box<dyn Fn(&[u8]) -> String + Send>;
 
-#[cfg(feature = "static-artifact-create")]
-const WASMER_METADATA_SYMBOL: &[u8] = b"WASMER_METADATA";
-
 impl Artifact {
     /// Compile a data buffer into a `ArtifactBuild`, which may then be instantiated.
     #[cfg(feature = "compiler")]
@@ -550,7 +547,8 @@ impl Artifact {
             )?;
         let mut obj = get_object_for_target(target_triple).map_err(to_compile_error)?;
 
-        emit_data(&mut obj, WASMER_METADATA_SYMBOL, &metadata_binary, 1)
+        let metadata_symbol = symbol_registry.symbol_to_name(Symbol::Metadata);
+        emit_data(&mut obj, metadata_symbol.as_bytes(), &metadata_binary, 1)
             .map_err(to_compile_error)?;
 
         emit_compilation(&mut obj, compilation, &symbol_registry, target_triple)
@@ -250,6 +250,17 @@ impl fmt::Debug for RuntimeError {
     }
 }
 
+/// The number of frames of the Wasm trace printed by default, which can be
+/// changed with the `WASMER_BACKTRACE_LIMIT` environment variable.
+const DEFAULT_BACKTRACE_LIMIT: usize = 50;
+
+fn backtrace_limit() -> usize {
+    std::env::var("WASMER_BACKTRACE_LIMIT")
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(DEFAULT_BACKTRACE_LIMIT)
+}
+
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "RuntimeError: {}", self.message())?;
@@ -257,7 +268,8 @@ impl fmt::Display for RuntimeError {
         if trace.is_empty() {
             return Ok(());
         }
-        for frame in self.trace().iter() {
+        let limit = backtrace_limit();
+        for frame in trace.iter().take(limit) {
             let name = frame.module_name();
             let func_index = frame.func_index();
             writeln!(f)?;
@@ -277,6 +289,10 @@ impl fmt::Display for RuntimeError {
                 frame.module_offset()
             )?;
         }
+        if trace.len() > limit {
+            writeln!(f)?;
+            write!(f, "    ... {} more frames", trace.len() - limit)?;
+        }
         Ok(())
     }
 }
@@ -13,7 +13,7 @@
 //! ```
 use std::cmp;
 use std::collections::BTreeMap;
-use std::sync::RwLock;
+use std::sync::{Once, RwLock};
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
 use wasmer_types::{LocalFunctionIndex, ModuleInfo};
@@ -168,6 +168,20 @@ impl Drop for GlobalFrameInfoRegistration {
     }
 }
 
+/// Returns whether a trap is registered for the instruction at `pc`.
+///
+/// This is called from signal handlers, so it doesn't wait for the frame
+/// information to be updated and assumes there is none if it is, leaving the
+/// fault to the previous signal handler.
+fn is_trap_pc(pc: usize) -> bool {
+    match FRAME_INFO.try_read() {
+        Ok(info) => info.lookup_trap_info(pc).is_some(),
+        Err(_) => false,
+    }
+}
+
+static TRAP_PC_LOOKUP: fn(usize) -> bool = is_trap_pc;
+
 /// Represents a continuous region of executable memory starting with a function
 /// entry point.
 #[derive(Debug)]
@@ -217,6 +231,8 @@ pub fn register(
         return None;
     }
 
+    static SET_TRAP_PC_LOOKUP: Once = Once::new();
+    SET_TRAP_PC_LOOKUP.call_once(|| wasmer_vm::set_trap_pc_lookup(&TRAP_PC_LOOKUP));
     let mut info = FRAME_INFO.write().unwrap();
     // First up assert that our chunk of jit functions doesn't collide with
     // any other known chunks of jit functions...
@@ -385,23 +385,33 @@ pub fn emit_compilation(
     Ok(())
 }
 
-/// Emit the compilation result into an existing object.
+/// Emit a serialized module into an existing object.
+///
+/// The module is emitted as two symbols, `{object_name}_LENGTH` holding its
+/// length and `{object_name}_DATA` holding its bytes, aligned to at least
+/// `align` bytes. Serialized modules need an alignment of
+/// [`MetadataHeader::ALIGN`](wasmer_types::MetadataHeader::ALIGN) to be
+/// deserialized in place.
 ///
 /// # Usage
 ///
 /// ```rust
-/// # use wasmer_types::SymbolRegistry;
-/// # use wasmer_types::{Compilation, Triple};
+/// # use wasmer_types::{MetadataHeader, Triple};
 /// # use wasmer_object::ObjectError;
-/// use wasmer_object::{get_object_for_target, emit_compilation};
+/// use wasmer_object::{get_object_for_target, emit_serialized};
 ///
-/// # fn emit_compilation_into_object(
+/// # fn emit_serialized_into_object(
 /// #     triple: &Triple,
-/// #     compilation: Compilation,
-/// #     symbol_registry: impl SymbolRegistry,
+/// #     serialized_module: &[u8],
 /// # ) -> Result<(), ObjectError> {
 /// let mut object = get_object_for_target(&triple)?;
-/// emit_compilation(&mut object, compilation, &symbol_registry, &triple)?;
+/// emit_serialized(
+///     &mut object,
+///     serialized_module,
+///     &triple,
+///     "WASMER_MODULE",
+///     MetadataHeader::ALIGN as u64,
+/// )?;
 /// # Ok(())
 /// # }
 /// ```
@@ -409,14 +419,16 @@ pub fn emit_serialized(
     obj: &mut Object,
     sercomp: &[u8],
     triple: &Triple,
+    object_name: &str,
+    align: u64,
 ) -> Result<(), ObjectError> {
     obj.set_mangling(object::write::Mangling::None);
     //let module_name = module.compile_info.module.name.clone();
-    let len_name = "WASMER_MODULE_LENGTH";
-    let data_name = "WASMER_MODULE_DATA";
+    let len_name = format!("{}_LENGTH", object_name);
+    let data_name = format!("{}_DATA", object_name);
     //let metadata_name = "WASMER_MODULE_METADATA";
 
-    let align = match triple.architecture {
+    let arch_align = match triple.architecture {
         Architecture::X86_64 => 1,
         // In Arm64 is recommended a 4-byte alignment
         Architecture::Aarch64(_) => 4,
@@ -435,7 +447,7 @@ pub fn emit_serialized(
         section: SymbolSection::Section(section_id),
         flags: SymbolFlags::None,
     });
-    obj.add_symbol_data(symbol_id, section_id, &len.to_le_bytes(), align);
+    obj.add_symbol_data(symbol_id, section_id, &len.to_le_bytes(), arch_align);
 
     let section_id = obj.section_id(StandardSection::Data);
     let symbol_id = obj.add_symbol(ObjSymbol {
@@ -448,7 +460,7 @@ pub fn emit_serialized(
         section: SymbolSection::Section(section_id),
         flags: SymbolFlags::None,
     });
-    obj.add_symbol_data(symbol_id, section_id, sercomp, align);
+    obj.add_symbol_data(symbol_id, section_id, sercomp, align.max(arch_align));
 
     Ok(())
 }
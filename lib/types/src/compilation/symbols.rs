@@ -40,6 +40,9 @@ pub enum Symbol {
 
     /// The dynamic function trampoline for a given function.
     DynamicFunctionTrampoline(FunctionIndex),
+
+    /// The serialized [`ModuleMetadata`] of the module.
+    Metadata,
 }
 
 /// This trait facilitates symbol name lookups in a native object file.
@@ -172,11 +175,21 @@ impl SymbolRegistry for ModuleMetadataSymbolRegistry {
                     index.index()
                 )
             }
+            Symbol::Metadata => {
+                if self.prefix.is_empty() {
+                    "WASMER_METADATA".to_string()
+                } else {
+                    format!("WASMER_METADATA_{}", self.prefix)
+                }
+            }
         }
     }
 
     fn name_to_symbol(&self, name: &str) -> Option<Symbol> {
-        if let Some(index) = name.strip_prefix(&format!("wasmer_function_{}_", self.prefix)) {
+        if name == self.symbol_to_name(Symbol::Metadata) {
+            Some(Symbol::Metadata)
+        } else if let Some(index) = name.strip_prefix(&format!("wasmer_function_{}_", self.prefix))
+        {
             index
                 .parse::<u32>()
                 .ok()
@@ -183,6 +183,12 @@ impl Target {
         }
     }
 
+    /// Creates a target for the host, with the CPU features detected
+    /// on the host.
+    pub fn from_host_with_features() -> Self {
+        Self::new(Triple::host(), CpuFeature::for_host())
+    }
+
     /// The triple associated for the target.
     pub fn triple(&self) -> &Triple {
         &self.triple
@@ -197,9 +203,6 @@ impl Target {
 /// The default for the Target will use the HOST as the triple
 impl Default for Target {
     fn default() -> Self {
-        Self {
-            triple: Triple::host(),
-            cpu_features: CpuFeature::for_host(),
-        }
+        Self::from_host_with_features()
     }
 }
@@ -9,9 +9,16 @@ mod trap;
 mod traphandlers;
 
 pub use trap::Trap;
+#[cfg(target_os = "windows")]
+pub use traphandlers::handle_exception;
+#[cfg(unix)]
+pub use traphandlers::handle_signal;
 pub use traphandlers::{
     catch_traps, on_host_stack, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
     TrapHandler, TrapHandlerFn,
 };
-pub use traphandlers::{init_traps, resume_panic};
+pub use traphandlers::{
+    init_traps, resume_panic, set_signal_handler_installation, set_trap_pc_lookup,
+    set_wasm_stack_size, DEFAULT_WASM_STACK_SIZE,
+};
 pub use wasmer_types::TrapCode;
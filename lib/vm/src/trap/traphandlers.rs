@@ -20,7 +20,7 @@ use std::mem;
 #[cfg(unix)]
 use std::mem::MaybeUninit;
 use std::ptr::{self, NonNull};
-use std::sync::atomic::{compiler_fence, AtomicPtr, Ordering};
+use std::sync::atomic::{compiler_fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 use std::sync::{Mutex, Once};
 use wasmer_types::TrapCode;
 
@@ -148,8 +148,11 @@ cfg_if::cfg_if! {
             }
 
             // On ARM, handle Unaligned Accesses.
-            // On Darwin, guard page accesses are raised as SIGBUS.
-            if cfg!(target_arch = "arm") || cfg!(target_vendor = "apple") {
+            // On Darwin and FreeBSD, guard page accesses are raised as SIGBUS.
+            if cfg!(target_arch = "arm")
+                || cfg!(target_vendor = "apple")
+                || cfg!(target_os = "freebsd")
+            {
                 register(&mut PREV_SIGBUS, libc::SIGBUS);
             }
 
@@ -203,6 +206,69 @@ cfg_if::cfg_if! {
                 libc::SIGILL => &PREV_SIGILL,
                 _ => panic!("unknown signal: {}", signum),
             };
+            if handle_signal(signum, siginfo, context) {
+                return;
+            }
+
+            // This signal is not for any compiled wasm code we expect, so we
+            // need to forward the signal to the next handler. If there is no
+            // next handler (SIG_IGN or SIG_DFL), then it's time to crash. To do
+            // this, we set the signal back to its original disposition and
+            // return. This will cause the faulting op to be re-executed which
+            // will crash in the normal way. If there is a next handler, call
+            // it. It will either crash synchronously, fix up the instruction
+            // so that execution can continue and return, or trigger a crash by
+            // returning the signal to it's original disposition and returning.
+            let previous = &*previous.as_ptr();
+            if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                mem::transmute::<
+                    usize,
+                    extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+                >(previous.sa_sigaction)(signum, siginfo, context)
+            } else if previous.sa_sigaction == libc::SIG_DFL
+            {
+                libc::sigaction(signum, previous, ptr::null_mut());
+            } else if previous.sa_sigaction != libc::SIG_IGN {
+                mem::transmute::<usize, extern "C" fn(libc::c_int)>(
+                    previous.sa_sigaction
+                )(signum)
+            }
+        }
+
+        /// Recovers from `signum` if it was raised by a trap in Wasm code,
+        /// returning whether it did.
+        ///
+        /// This is what wasmer's own signal handler does before forwarding
+        /// the signal to the previous handler. Embedders that disabled it with
+        /// [`set_signal_handler_installation`] can call this from their own
+        /// `SA_SIGINFO` handler for `SIGSEGV`, `SIGBUS`, `SIGILL` and `SIGFPE`,
+        /// and handle the signal themselves if this returns `false`.
+        ///
+        /// That handler should be installed with `SA_ONSTACK`, as stack
+        /// overflows in Wasm code are reported by `SIGSEGV`, and call this
+        /// before anything else. Like any signal handler, it must only do
+        /// async-signal-safe work.
+        ///
+        /// When this returns `true`, `context` has been updated to resume at
+        /// the point where the trap is reported, and the handler must return
+        /// without touching it. When this returns `false`, the signal doesn't
+        /// come from Wasm code and nothing has been changed: the handler deals
+        /// with it as if wasmer wasn't there, for instance by chaining to the
+        /// previous handler, or by restoring the default disposition and
+        /// returning so that the fault is raised again and kills the process.
+        ///
+        /// # Safety
+        ///
+        /// Must only be called from a signal handler, with the arguments it
+        /// received.
+        pub unsafe fn handle_signal(
+            signum: libc::c_int,
+            siginfo: *mut libc::siginfo_t,
+            context: *mut libc::c_void,
+        ) -> bool {
+            if !matches!(signum, libc::SIGSEGV | libc::SIGBUS | libc::SIGFPE | libc::SIGILL) {
+                return false;
+            }
             // We try to get the fault address associated to this signal
             let maybe_fault_address = match signum {
                 libc::SIGSEGV | libc::SIGBUS => {
@@ -216,45 +282,50 @@ cfg_if::cfg_if! {
                     let addr = (*siginfo).si_addr() as usize;
                     process_illegal_op(addr)
                 }
+                // Only used if no trap was registered at the faulting instruction. x86
+                // raises the same fault for a division by zero and an overflowing `idiv`,
+                // which Linux reports as `FPE_INTDIV`, so only an explicit `FPE_INTOVF`
+                // is reported as an overflow.
+                libc::SIGFPE => Some(fpe_trap_code((*siginfo).si_code)),
                 _ => None,
             };
             let ucontext = &mut *(context as *mut libc::ucontext_t);
             let (pc, sp) = get_pc_sp(ucontext);
-            let handled = TrapHandlerContext::handle_trap(
+            TrapHandlerContext::handle_trap(
                 pc,
                 sp,
                 maybe_fault_address,
                 trap_code,
+                is_unmapped_access(signum, (*siginfo).si_code),
                 |regs| update_context(ucontext, regs),
                 |handler| handler(signum, siginfo, context),
-            );
+            )
+        }
 
-            if handled {
-                return;
+        /// Returns whether `signum` reports an access to an address that isn't
+        /// mapped at all.
+        ///
+        /// Linear memories and Wasm stacks are guarded by pages that are mapped
+        /// without any access rights, which Linux reports as `SEGV_ACCERR`. A
+        /// `SEGV_MAPERR` can't come from an out-of-bounds Wasm access, so it is
+        /// a bug in the host that must not be turned into a trap.
+        fn is_unmapped_access(signum: libc::c_int, si_code: libc::c_int) -> bool {
+            cfg_if::cfg_if! {
+                if #[cfg(any(target_os = "linux", target_os = "android"))] {
+                    signum == libc::SIGSEGV && si_code == libc::SEGV_MAPERR
+                } else {
+                    let _ = (signum, si_code);
+                    false
+                }
             }
+        }
 
-            // This signal is not for any compiled wasm code we expect, so we
-            // need to forward the signal to the next handler. If there is no
-            // next handler (SIG_IGN or SIG_DFL), then it's time to crash. To do
-            // this, we set the signal back to its original disposition and
-            // return. This will cause the faulting op to be re-executed which
-            // will crash in the normal way. If there is a next handler, call
-            // it. It will either crash synchronously, fix up the instruction
-            // so that execution can continue and return, or trigger a crash by
-            // returning the signal to it's original disposition and returning.
-            let previous = &*previous.as_ptr();
-            if previous.sa_flags & libc::SA_SIGINFO != 0 {
-                mem::transmute::<
-                    usize,
-                    extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
-                >(previous.sa_sigaction)(signum, siginfo, context)
-            } else if previous.sa_sigaction == libc::SIG_DFL
-            {
-                libc::sigaction(signum, previous, ptr::null_mut());
-            } else if previous.sa_sigaction != libc::SIG_IGN {
-                mem::transmute::<usize, extern "C" fn(libc::c_int)>(
-                    previous.sa_sigaction
-                )(signum)
+        /// Maps the `si_code` of a `SIGFPE` to the trap it reports.
+        fn fpe_trap_code(si_code: libc::c_int) -> TrapCode {
+            if si_code == libc::FPE_INTOVF {
+                TrapCode::IntegerOverflow
+            } else {
+                TrapCode::IntegerDivisionByZero
             }
         }
 
@@ -273,7 +344,7 @@ cfg_if::cfg_if! {
                 ))] {
                     pc = context.uc_mcontext.gregs[libc::REG_EIP as usize] as usize;
                     sp = context.uc_mcontext.gregs[libc::REG_ESP as usize] as usize;
-                } else if #[cfg(all(target_os = "freebsd", target_arch = "x86"))] {
+                } else if #[cfg(all(target_os = "freebsd", target_arch = "x86_64"))] {
                     pc = context.uc_mcontext.mc_rip as usize;
                     sp = context.uc_mcontext.mc_rsp as usize;
                 } else if #[cfg(all(target_vendor = "apple", target_arch = "x86_64"))] {
@@ -409,12 +480,13 @@ cfg_if::cfg_if! {
                     (*context.uc_mcontext).__ss.__fp = x29;
                     (*context.uc_mcontext).__ss.__lr = lr;
                 } else if #[cfg(all(target_os = "freebsd", target_arch = "aarch64"))] {
-                    context.uc_mcontext.mc_gpregs.gp_pc = pc as libc::register_t;
+                    let TrapHandlerRegs { pc, sp, x0, x1, x29, lr } = regs;
+                    context.uc_mcontext.mc_gpregs.gp_elr = pc as libc::register_t;
                     context.uc_mcontext.mc_gpregs.gp_sp = sp as libc::register_t;
                     context.uc_mcontext.mc_gpregs.gp_x[0] = x0 as libc::register_t;
                     context.uc_mcontext.mc_gpregs.gp_x[1] = x1 as libc::register_t;
                     context.uc_mcontext.mc_gpregs.gp_x[29] = x29 as libc::register_t;
-                    context.uc_mcontext.mc_gpregs.gp_x[30] = lr as libc::register_t;
+                    context.uc_mcontext.mc_gpregs.gp_lr = lr as libc::register_t;
                 } else {
                     compile_error!("Unsupported platform");
                 }
@@ -438,6 +510,26 @@ cfg_if::cfg_if! {
         unsafe extern "system" fn exception_handler(
             exception_info: PEXCEPTION_POINTERS
         ) -> LONG {
+            if handle_exception(exception_info) {
+                EXCEPTION_CONTINUE_EXECUTION
+            } else {
+                EXCEPTION_CONTINUE_SEARCH
+            }
+        }
+
+        /// Recovers from the exception if it was raised by a trap in Wasm
+        /// code, returning whether it did.
+        ///
+        /// This is what wasmer's own vectored exception handler does.
+        /// Embedders that disabled it with [`set_signal_handler_installation`]
+        /// can call this from their own handler, and continue execution if it
+        /// returns `true`.
+        ///
+        /// # Safety
+        ///
+        /// Must only be called from an exception handler, with the exception
+        /// information it received.
+        pub unsafe fn handle_exception(exception_info: PEXCEPTION_POINTERS) -> bool {
             // Check the kind of exception, since we only handle a subset within
             // wasm code. If anything else happens we want to defer to whatever
             // the rest of the system wants to do for this exception.
@@ -448,7 +540,7 @@ cfg_if::cfg_if! {
                 record.ExceptionCode != EXCEPTION_INT_DIVIDE_BY_ZERO &&
                 record.ExceptionCode != EXCEPTION_INT_OVERFLOW
             {
-                return EXCEPTION_CONTINUE_SEARCH;
+                return false;
             }
 
             // FIXME: this is what the previous C++ did to make sure that TLS
@@ -477,24 +569,22 @@ cfg_if::cfg_if! {
                 EXCEPTION_ILLEGAL_INSTRUCTION => {
                     process_illegal_op(pc)
                 }
+                // Only used if no trap was registered at the faulting instruction.
+                EXCEPTION_INT_DIVIDE_BY_ZERO => Some(TrapCode::IntegerDivisionByZero),
+                EXCEPTION_INT_OVERFLOW => Some(TrapCode::IntegerOverflow),
                 _ => None,
             };
             // This is basically the same as the unix version above, only with a
             // few parameters tweaked here and there.
-            let handled = TrapHandlerContext::handle_trap(
+            TrapHandlerContext::handle_trap(
                 pc,
                 sp,
                 maybe_fault_address,
                 trap_code,
+                false,
                 |regs| update_context(context, regs),
                 |handler| handler(exception_info),
-            );
-
-            if handled {
-                EXCEPTION_CONTINUE_EXECUTION
-            } else {
-                EXCEPTION_CONTINUE_SEARCH
-            }
+            )
         }
 
         unsafe fn get_pc_sp(context: &CONTEXT) -> (usize, usize) {
@@ -504,8 +594,8 @@ cfg_if::cfg_if! {
                     pc = context.Rip as usize;
                     sp = context.Rsp as usize;
                 } else if #[cfg(target_arch = "x86")] {
-                    pc = context.Rip as usize;
-                    sp = context.Rsp as usize;
+                    pc = context.Eip as usize;
+                    sp = context.Esp as usize;
                 } else {
                     compile_error!("Unsupported platform");
                 }
@@ -548,10 +638,65 @@ cfg_if::cfg_if! {
 pub fn init_traps() {
     static INIT: Once = Once::new();
     INIT.call_once(|| unsafe {
-        platform_init();
+        if INSTALL_SIGNAL_HANDLERS.load(Ordering::SeqCst) {
+            platform_init();
+        }
     });
 }
 
+static INSTALL_SIGNAL_HANDLERS: AtomicBool = AtomicBool::new(true);
+
+/// Sets whether [`init_traps`] installs wasmer's signal handlers (the vectored
+/// exception handler on Windows), which it does by default.
+///
+/// This must be called before the first `Store` is created. When disabled, traps
+/// in Wasm code are only recovered from if the embedder's own handlers call
+/// `handle_signal` (`handle_exception` on Windows); otherwise they crash the
+/// process.
+pub fn set_signal_handler_installation(enabled: bool) {
+    INSTALL_SIGNAL_HANDLERS.store(enabled, Ordering::SeqCst);
+}
+
+/// The size of the stack Wasm code runs on by default: 1 MiB.
+pub const DEFAULT_WASM_STACK_SIZE: usize = 1 << 20;
+
+static WASM_STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_WASM_STACK_SIZE);
+
+/// Sets the size in bytes of the stacks Wasm code runs on, which is
+/// [`DEFAULT_WASM_STACK_SIZE`] by default.
+///
+/// Wasm code runs on its own stack rather than on the stack of the calling
+/// thread, so the depth of the guest's recursion is bounded by this size
+/// alone; going past it traps with a stack overflow. Calls already running
+/// keep the stack they started with.
+///
+/// # Panics
+///
+/// Panics if `size` is zero.
+pub fn set_wasm_stack_size(size: usize) {
+    assert!(size > 0, "the Wasm stack size must not be zero");
+    WASM_STACK_SIZE.store(size, Ordering::SeqCst);
+}
+
+static TRAP_PC_LOOKUP: AtomicPtr<fn(usize) -> bool> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the function the signal handler calls to tell whether a trap is
+/// registered for the instruction at a program counter.
+///
+/// The engine sets this once, before it registers the frame information of
+/// compiled code for the first time. It is called from signal handlers, so it
+/// must not block.
+pub fn set_trap_pc_lookup(lookup: &'static fn(usize) -> bool) {
+    TRAP_PC_LOOKUP.store(lookup as *const _ as *mut _, Ordering::SeqCst);
+}
+
+/// Returns whether a trap is registered for the instruction at `pc`.
+fn is_registered_trap_pc(pc: usize) -> bool {
+    let lookup = TRAP_PC_LOOKUP.load(Ordering::SeqCst);
+    // The pointer comes from the `&'static` given to `set_trap_pc_lookup`.
+    !lookup.is_null() && unsafe { (*lookup)(pc) }
+}
+
 /// Raises a user-defined trap immediately.
 ///
 /// This function performs as-if a wasm trap was just executed, only the trap
@@ -667,6 +812,7 @@ struct TrapHandlerContext {
         usize,
         Option<usize>,
         Option<TrapCode>,
+        bool,
         &mut dyn FnMut(TrapHandlerRegs),
     ) -> bool,
     custom_trap: Option<*const TrapHandlerFn<'static>>,
@@ -692,6 +838,7 @@ impl TrapHandlerContext {
             sp: usize,
             maybe_fault_address: Option<usize>,
             trap_code: Option<TrapCode>,
+            unmapped_access: bool,
             update_regs: &mut dyn FnMut(TrapHandlerRegs),
         ) -> bool {
             unsafe {
@@ -700,6 +847,7 @@ impl TrapHandlerContext {
                     sp,
                     maybe_fault_address,
                     trap_code,
+                    unmapped_access,
                     update_regs,
                 )
             }
@@ -732,6 +880,7 @@ impl TrapHandlerContext {
         sp: usize,
         maybe_fault_address: Option<usize>,
         trap_code: Option<TrapCode>,
+        unmapped_access: bool,
         mut update_regs: impl FnMut(TrapHandlerRegs),
         call_handler: impl Fn(&TrapHandlerFn<'static>) -> bool,
     ) -> bool {
@@ -755,6 +904,7 @@ impl TrapHandlerContext {
             sp,
             maybe_fault_address,
             trap_code,
+            unmapped_access,
             &mut update_regs,
         )
     }
@@ -767,6 +917,7 @@ impl<T> TrapHandlerContextInner<T> {
         sp: usize,
         maybe_fault_address: Option<usize>,
         trap_code: Option<TrapCode>,
+        unmapped_access: bool,
         update_regs: &mut dyn FnMut(TrapHandlerRegs),
     ) -> bool {
         // Check if this trap occurred while executing on the Wasm stack. We can
@@ -775,6 +926,19 @@ impl<T> TrapHandlerContextInner<T> {
             return false;
         }
 
+        // Out-of-bounds accesses to linear memory fault on guard pages. Leave
+        // any other access to unmapped memory to the previous signal handler
+        // unless it overflowed the stack or comes from an instruction a trap
+        // is registered for, as it is a bug in the host.
+        if unmapped_access
+            && !maybe_fault_address.map_or(false, |addr| {
+                self.coro_trap_handler.stack_ptr_in_bounds(addr)
+            })
+            && !is_registered_trap_pc(pc)
+        {
+            return false;
+        }
+
         let signal_trap = trap_code.or_else(|| {
             maybe_fault_address.map(|addr| {
                 if self.coro_trap_handler.stack_ptr_in_bounds(addr) {
@@ -865,13 +1029,26 @@ fn on_wasm_stack<F: FnOnce() -> T, T>(
     // allows them to be reused multiple times.
     // FIXME(Amanieu): We should refactor this to avoid the lock.
     lazy_static::lazy_static! {
-        static ref STACK_POOL: Mutex<Vec<DefaultStack>> = Mutex::new(vec![]);
+        static ref STACK_POOL: Mutex<Vec<(usize, DefaultStack)>> = Mutex::new(vec![]);
     }
-    let stack = STACK_POOL.lock().unwrap().pop().unwrap_or_default();
+    let stack_size = WASM_STACK_SIZE.load(Ordering::SeqCst);
+    let stack = {
+        let mut pool = STACK_POOL.lock().unwrap();
+        // Stacks allocated before the size was changed are not reused.
+        pool.retain(|(size, _)| *size == stack_size);
+        pool.pop()
+    };
+    let stack = match stack {
+        Some(stack) => stack,
+        None => (
+            stack_size,
+            DefaultStack::new(stack_size).map_err(|e| UnwindReason::UserTrap(Box::new(e)))?,
+        ),
+    };
     let mut stack = scopeguard::guard(stack, |stack| STACK_POOL.lock().unwrap().push(stack));
 
     // Create a coroutine with a new stack to run the function on.
-    let mut coro = ScopedCoroutine::with_stack(&mut *stack, move |yielder, ()| {
+    let mut coro = ScopedCoroutine::with_stack(&mut stack.1, move |yielder, ()| {
         // Save the yielder to TLS so that it can be used later.
         YIELDER.with(|cell| cell.set(Some(yielder.into())));
 
@@ -1054,3 +1231,44 @@ pub fn lazy_per_thread_init() -> Result<(), Trap> {
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fpe_trap_code() {
+        assert_eq!(fpe_trap_code(libc::FPE_INTOVF), TrapCode::IntegerOverflow);
+        assert_eq!(
+            fpe_trap_code(libc::FPE_INTDIV),
+            TrapCode::IntegerDivisionByZero
+        );
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_sigfpe_without_registered_trap() {
+        init_traps();
+        // Nothing registers a trap for this `idiv`, so the trap is the one its `si_code`
+        // maps to. x86 raises the same fault for an overflow as for a division by zero.
+        let result = unsafe {
+            catch_traps(None, || {
+                let quotient: i32;
+                std::arch::asm!(
+                    "cdq",
+                    "idiv {divisor:e}",
+                    divisor = in(reg) -1i32,
+                    inout("eax") i32::MIN => quotient,
+                    out("edx") _,
+                );
+                quotient
+            })
+        };
+        match result {
+            Err(Trap::Wasm { signal_trap, .. }) => {
+                assert_eq!(signal_trap, Some(TrapCode::IntegerDivisionByZero))
+            }
+            other => panic!("expected a trap, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Traps are recovered from by a signal handler of the embedder that forwards
+//! signals to `handle_signal` when wasmer doesn't install its own handlers.
+//!
+//! This is its own test binary because whether the handlers are installed is
+//! decided once per process.
+#![cfg(all(unix, target_arch = "x86_64"))]
+
+use std::arch::asm;
+use std::{mem, ptr};
+use wasmer_vm::{
+    catch_traps, handle_signal, init_traps, set_signal_handler_installation, Trap, TrapCode,
+};
+
+extern "C" fn embedder_handler(
+    signum: libc::c_int,
+    siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    unsafe {
+        if !handle_signal(signum, siginfo, context) {
+            // Not a trap: raise the fault again, with the default disposition.
+            libc::signal(signum, libc::SIG_DFL);
+        }
+    }
+}
+
+/// Divides `i32::MIN` by `-1` with `idiv`, which raises `SIGFPE`.
+fn overflowing_division() -> i32 {
+    let quotient: i32;
+    unsafe {
+        asm!(
+            "cdq",
+            "idiv {divisor:e}",
+            divisor = in(reg) -1i32,
+            inout("eax") i32::MIN => quotient,
+            out("edx") _,
+        );
+    }
+    quotient
+}
+
+#[test]
+fn embedder_signal_handler_recovers_traps() {
+    set_signal_handler_installation(false);
+    init_traps();
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = embedder_handler as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(libc::sigaction(libc::SIGFPE, &action, ptr::null_mut()), 0);
+    }
+
+    match unsafe { catch_traps(None, overflowing_division) } {
+        Err(Trap::Wasm { signal_trap, .. }) => {
+            assert_eq!(signal_trap, Some(TrapCode::IntegerDivisionByZero))
+        }
+        other => panic!("expected a trap, got {:?}", other),
+    }
+}
@@ -159,6 +159,243 @@ fn test_trap_stack_overflow(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+lazy_static::lazy_static! {
+    /// Serializes the tests that change the process-wide Wasm stack size.
+    static ref WASM_STACK_SIZE_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+}
+
+/// Restores the default Wasm stack size when dropped.
+struct DefaultWasmStackSize;
+
+impl Drop for DefaultWasmStackSize {
+    fn drop(&mut self) {
+        wasmer::vm::set_wasm_stack_size(wasmer::vm::DEFAULT_WASM_STACK_SIZE);
+    }
+}
+
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(traps)]
+fn test_trap_stack_overflow_larger_stack(config: crate::Config) -> Result<()> {
+    let _lock = WASM_STACK_SIZE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    // Each call keeps 32 values loaded from memory live across the recursive
+    // call, so that no compiler can keep its frame under 256 bytes, and 5000
+    // calls take more than 1 MiB but well under 8 MiB of stack.
+    const LIVE_VALUES: usize = 32;
+    let locals = (0..LIVE_VALUES).map(|_| " i64").collect::<String>();
+    let loads = (0..LIVE_VALUES)
+        .map(|i| {
+            format!(
+                "(local.set {} (i64.load offset={} (i32.const 0)))",
+                i + 1,
+                i * 8
+            )
+        })
+        .collect::<String>();
+    let sums = (0..LIVE_VALUES)
+        .map(|i| format!("(i64.add (local.get {}))", i + 1))
+        .collect::<String>();
+    let wat = format!(
+        r#"
+        (module
+            (memory 1)
+            (func $rec (export "rec") (param i32) (result i64)
+                (local{})
+                (if (result i64) (i32.eqz (local.get 0))
+                    (then (i64.const 0))
+                    (else
+                        {}
+                        (call $rec (i32.sub (local.get 0) (i32.const 1)))
+                        {})))
+        )
+    "#,
+        locals, loads, sums
+    );
+
+    let mut store = config.store();
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let rec = instance.exports.get_function("rec")?;
+
+    let e = rec
+        .call(&mut store, &[Value::I32(5000)])
+        .err()
+        .expect("5000 calls didn't overflow the default stack");
+    assert!(e.message().contains("call stack exhausted"));
+
+    let _default = DefaultWasmStackSize;
+    wasmer::vm::set_wasm_stack_size(8 * wasmer::vm::DEFAULT_WASM_STACK_SIZE);
+    assert_eq!(
+        &*rec.call(&mut store, &[Value::I32(5000)])?,
+        &[Value::I64(0)]
+    );
+
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn test_trap_memory_out_of_bounds(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "run") (result i32)
+                i32.const 0x10000
+                i32.load)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[])
+        .err()
+        .expect("error calling function");
+
+    assert_eq!(e.message(), "out of bounds memory access");
+
+    Ok(())
+}
+
+// The access hits the guard pages of the linear memory, so the trap is raised by the
+// signal handler, which reads the registers from FreeBSD's own `mcontext_t`.
+#[cfg(target_os = "freebsd")]
+#[compiler_test(traps)]
+fn test_trap_memory_out_of_bounds_freebsd(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "run") (result i32)
+                i32.const 0x7fff0000
+                i32.load)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[])
+        .err()
+        .expect("error calling function");
+
+    assert_eq!(e.message(), "out of bounds memory access");
+
+    Ok(())
+}
+
+// Linux tells faults on guard pages from ones on unmapped memory, and only the
+// latter are left to the previous signal handler.
+#[cfg(target_os = "linux")]
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(traps)]
+fn test_trap_guard_page_faults(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (memory 1)
+            (func (export "load") (result i32)
+                i32.const 0xffff0000
+                i32.load offset=0x7fff0000)
+            (func $recurse (export "recurse") (param i64) (result i64)
+                (local i64 i64 i64 i64 i64 i64 i64 i64)
+                local.get 0
+                call $recurse)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+
+    let e = instance
+        .exports
+        .get_function("load")?
+        .call(&mut store, &[])
+        .err()
+        .expect("error calling function");
+    assert_eq!(e.message(), "out of bounds memory access");
+
+    let e = instance
+        .exports
+        .get_function("recurse")?
+        .call(&mut store, &[Value::I64(0)])
+        .err()
+        .expect("error calling function");
+    assert!(e.message().contains("call stack exhausted"));
+
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn test_trap_integer_division_by_zero(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func (export "run") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.div_s)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[Value::I32(1), Value::I32(0)])
+        .err()
+        .expect("error calling function");
+
+    assert_eq!(e.message(), "integer divide by zero");
+
+    Ok(())
+}
+
+#[compiler_test(traps)]
+fn test_trap_integer_overflow(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module
+            (func (export "run") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.div_s)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[Value::I32(i32::MIN), Value::I32(-1)])
+        .err()
+        .expect("error calling function");
+
+    assert_eq!(e.message(), "integer overflow");
+
+    Ok(())
+}
+
 #[cfg_attr(target_env = "musl", ignore)]
 #[compiler_test(traps)]
 fn trap_display_pretty(config: crate::Config) -> Result<()> {
@@ -195,6 +432,49 @@ RuntimeError: unreachable
     Ok(())
 }
 
+#[cfg_attr(target_env = "musl", ignore)]
+#[compiler_test(traps)]
+fn trap_display_limits_frames(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let wat = r#"
+        (module $m
+            (global $depth (mut i32) (i32.const 0))
+            (func $countdown (export "run") (param i32)
+                local.get 0
+                i32.eqz
+                if
+                    unreachable
+                end
+                local.get 0
+                i32.const 1
+                i32.sub
+                call $countdown
+                ;; Keeps the recursive call from being turned into a loop.
+                global.get $depth
+                i32.const 1
+                i32.add
+                global.set $depth)
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&mut store, &module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func
+        .call(&mut store, &[Value::I32(99)])
+        .err()
+        .expect("error calling function");
+    assert_eq!(e.trace().len(), 100);
+    let display = e.to_string();
+    assert_eq!(display.matches("    at countdown").count(), 50);
+    assert!(display.ends_with("    ... 50 more frames"), "{}", display);
+    Ok(())
+}
+
 #[cfg_attr(target_env = "musl", ignore)]
 #[compiler_test(traps)]
 fn trap_display_multi_module(config: crate::Config) -> Result<()> {
@@ -337,6 +617,52 @@ fn rust_panic_import(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+#[compiler_test(traps)]
+fn rust_panic_import_then_call(config: crate::Config) -> Result<()> {
+    let mut store = config.store();
+    let binary = r#"
+        (module $a
+            (import "" "foo" (func $foo))
+            (func (export "foo") call $foo)
+            (func (export "answer") (result i32) i32.const 42)
+            (func (export "trap") unreachable)
+        )
+    "#;
+
+    let module = Module::new(&store, &binary)?;
+    let sig = FunctionType::new(vec![], vec![]);
+    let func = Function::new(&mut store, &sig, |_| panic!("this is a panic"));
+    let instance = Instance::new(
+        &mut store,
+        &module,
+        &imports! {
+            "" => {
+                "foo" => func,
+            }
+        },
+    )?;
+    let func = instance.exports.get_function("foo")?.clone();
+    let err = panic::catch_unwind(AssertUnwindSafe(|| {
+        drop(func.call(&mut store, &[]));
+    }))
+    .unwrap_err();
+    assert_eq!(err.downcast_ref::<&'static str>(), Some(&"this is a panic"));
+
+    // The trap handler state unwound with the panic, so later calls still work...
+    let answer = instance.exports.get_function("answer")?;
+    assert_eq!(&*answer.call(&mut store, &[])?, &[Value::I32(42)]);
+
+    // ...and their traps are still caught.
+    let trap = instance.exports.get_function("trap")?;
+    let e = trap
+        .call(&mut store, &[])
+        .err()
+        .expect("error calling function");
+    assert!(e.message().contains("unreachable"), "{}", e.message());
+
+    Ok(())
+}
+
 #[compiler_test(traps)]
 fn rust_panic_start_function(config: crate::Config) -> Result<()> {
     let mut store = config.store();
@@ -28,6 +28,8 @@ struct WasmerCreateExe {
     compiler: Compiler,
     /// Extra CLI flags
     extra_cli_flags: Vec<&'static str>,
+    /// Environment variables set for the command
+    envs: Vec<(&'static str, &'static str)>,
 }
 
 impl Default for WasmerCreateExe {
@@ -43,6 +45,7 @@ impl Default for WasmerCreateExe {
             native_executable_path,
             compiler: Compiler::Cranelift,
             extra_cli_flags: vec![],
+            envs: vec![],
         }
     }
 }
@@ -57,6 +60,7 @@ impl WasmerCreateExe {
             .args(self.extra_cli_flags.iter())
             .arg("-o")
             .arg(&self.native_executable_path)
+            .envs(self.envs.iter().copied())
             .output()?;
 
         if !output.status.success() {
@@ -164,6 +168,99 @@ fn create_exe_works() -> anyhow::Result<()> {
     Ok(())
 }
 
+// Needs `libzstd` to link the executable.
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_compress_modules_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--compress-modules"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_output_directory() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let output_dir = operating_dir.join("out");
+    std::fs::create_dir(&output_dir)?;
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: output_dir.clone(),
+        compiler: Compiler::Cranelift,
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    // The executable is named after the input.
+    #[cfg(not(windows))]
+    let executable_path = output_dir.join("qjs");
+    #[cfg(windows)]
+    let executable_path = output_dir.join("qjs.exe");
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(windows)]
+fn create_exe_adds_exe_extension_on_windows() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: operating_dir.join("wasm"),
+        compiler: Compiler::Cranelift,
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    assert!(operating_dir.join("wasm.exe").is_file());
+    assert!(!operating_dir.join("wasm").exists());
+
+    Ok(())
+}
+
 #[test]
 fn create_exe_works_with_file() -> anyhow::Result<()> {
     let temp_dir = tempfile::tempdir()?;
@@ -257,14 +354,274 @@ fn create_exe_serialized_works() -> anyhow::Result<()> {
 
     let output_str = String::from_utf8_lossy(&output);
     assert!(
-        output_str.contains("Serialized"),
-        "create-exe output doesn't mention `serialized` format keyword:\n{}",
+        !output_str.contains("Serialized"),
+        "create-exe printed its build plan without `-v`:\n{}",
         output_str
     );
 
     Ok(())
 }
 
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_linkage() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    for object_format in ["serialized", "symbols"] {
+        for linkage in ["--static", "--dynamic"] {
+            WasmerCreateExe {
+                current_dir: operating_dir.clone(),
+                wasm_path: wasm_path.clone(),
+                native_executable_path: executable_path.clone(),
+                compiler: Compiler::Cranelift,
+                extra_cli_flags: vec!["--object-format", object_format, linkage],
+                ..Default::default()
+            }
+            .run()
+            .with_context(|| {
+                format!(
+                    "Failed to create-exe wasm with Wasmer: {} {}",
+                    object_format, linkage
+                )
+            })?;
+
+            let result = run_code(
+                &operating_dir,
+                &executable_path,
+                &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+            )
+            .context("Failed to run generated executable")?;
+            let result_lines = result.lines().collect::<Vec<&str>>();
+            assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+            let dynamic_section = Command::new("readelf")
+                .arg("-d")
+                .arg(&executable_path)
+                .output()
+                .context("Failed to run readelf")?;
+            let dynamic_section = String::from_utf8_lossy(&dynamic_section.stdout);
+            let needs_libwasmer = dynamic_section
+                .lines()
+                .any(|line| line.contains("(NEEDED)") && line.contains("libwasmer"));
+            assert_eq!(
+                needs_libwasmer,
+                linkage == "--dynamic",
+                "{} {}:\n{}",
+                object_format,
+                linkage,
+                dynamic_section
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_serialized_rejects_target() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let error = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        native_executable_path: operating_dir.join("wasm.out"),
+        extra_cli_flags: vec![
+            "--object-format",
+            "serialized",
+            "--target",
+            "aarch64-unknown-linux-gnu",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .expect_err("create-exe cross-compiled the serialized object format");
+    assert!(
+        error
+            .to_string()
+            .contains("Cross-compilation with serialized object format is not implemented."),
+        "unexpected create-exe error: {}",
+        error
+    );
+    assert!(!operating_dir.join("wasm.out").exists());
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_strip_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--strip"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_serialized_multiple_modules() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    fs::copy(create_exe_test_wasm_path(), operating_dir.join("a.wasm"))?;
+    fs::copy(create_exe_test_wasm_path(), operating_dir.join("b.wasm"))?;
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: operating_dir.join("a.wasm"),
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "b.wasm",
+            "--object-format",
+            "serialized",
+            "--emit-manifest",
+            "manifest.json",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    // `a.wasm` and `b.wasm` are identical, so they share a single embedded module.
+    let manifest = fs::read_to_string(operating_dir.join("manifest.json"))?;
+    assert_eq!(
+        manifest.matches("\"WASMER_MODULE_0\"").count(),
+        2,
+        "{}",
+        manifest
+    );
+    assert!(!manifest.contains("\"WASMER_MODULE_1\""), "{}", manifest);
+    assert!(manifest.contains("\"name\": \"b\""), "{}", manifest);
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["b".to_string(), "--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_serialized_precompiled_atom() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let create_exe = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--object-format",
+            "serialized",
+            "--precompiled-atom",
+            "precompiled",
+        ],
+        ..Default::default()
+    };
+    create_exe
+        .run()
+        .context("Failed to create-exe wasm with Wasmer")?;
+
+    let precompiled = fs::read_dir(operating_dir.join("precompiled"))?.count();
+    assert_eq!(precompiled, 1);
+
+    // The second build reuses the serialized module instead of adding a new one.
+    create_exe
+        .run()
+        .context("Failed to create-exe wasm with Wasmer")?;
+    assert_eq!(
+        fs::read_dir(operating_dir.join("precompiled"))?.count(),
+        precompiled
+    );
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_cache_dir() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--object-format", "serialized", "--cache-dir", "cache"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    // The serialized module is cached under `<cache dir>/<version>/create-exe`.
+    let cached = fs::read_dir(operating_dir.join("cache"))?
+        .map(|version_dir| -> anyhow::Result<usize> {
+            Ok(fs::read_dir(version_dir?.path().join("create-exe"))?.count())
+        })
+        .sum::<anyhow::Result<usize>>()?;
+    assert_eq!(cached, 1);
+
+    Ok(())
+}
+
 fn create_obj(args: Vec<&'static str>, keyword_needle: &str, keyword: &str) -> anyhow::Result<()> {
     let temp_dir = tempfile::tempdir()?;
     let operating_dir: PathBuf = temp_dir.path().to_owned();
@@ -330,6 +687,30 @@ fn create_obj_serialized() -> anyhow::Result<()> {
     )
 }
 
+#[test]
+fn create_obj_llvm_ir_requires_llvm() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let result = WasmerCreateObj {
+        current_dir: operating_dir.clone(),
+        wasm_path: operating_dir.join(create_exe_test_wasm_path()),
+        output_object_path: operating_dir.join("wasm.ll"),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--object-format", "llvm-ir"],
+        ..Default::default()
+    }
+    .run();
+    let error = result.expect_err("create-obj emitted LLVM IR without the LLVM compiler");
+    assert!(
+        error.to_string().contains("requires the LLVM compiler"),
+        "unexpected create-obj error: {}",
+        error
+    );
+
+    Ok(())
+}
+
 fn create_exe_with_object_input(args: Vec<&'static str>) -> anyhow::Result<()> {
     let temp_dir = tempfile::tempdir()?;
     let operating_dir: PathBuf = temp_dir.path().to_owned();
@@ -407,3 +788,1203 @@ fn create_exe_with_object_input_symbols() -> anyhow::Result<()> {
 fn create_exe_with_object_input_serialized() -> anyhow::Result<()> {
     create_exe_with_object_input(vec!["--object-format", "serialized"])
 }
+
+#[test]
+fn create_exe_with_prefixed_object_input_symbols() -> anyhow::Result<()> {
+    create_exe_with_object_input(vec![
+        "--object-format",
+        "symbols",
+        "--symbol-prefix",
+        "qjs_",
+    ])
+}
+
+#[test]
+fn create_exe_with_prefixed_object_input_serialized() -> anyhow::Result<()> {
+    create_exe_with_object_input(vec![
+        "--object-format",
+        "serialized",
+        "--symbol-prefix",
+        "qjs_",
+    ])
+}
+
+#[test]
+#[cfg(unix)]
+fn create_obj_symbols_objects_link_together() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let mut object_paths = Vec::new();
+    for wasm in ["qjs.wasm", "call_trap.wasm"] {
+        let object_path = operating_dir.join(wasm).with_extension("o");
+        WasmerCreateObj {
+            current_dir: operating_dir.clone(),
+            wasm_path: PathBuf::from(C_ASSET_PATH).join(wasm),
+            output_object_path: object_path.clone(),
+            compiler: Compiler::Cranelift,
+            extra_cli_flags: vec!["--object-format", "symbols"],
+            ..Default::default()
+        }
+        .run()
+        .context("Failed to create-obj wasm with Wasmer")?;
+        object_paths.push(object_path);
+    }
+
+    // A relocatable link fails on symbols defined by both objects.
+    let output = Command::new("ld")
+        .arg("-r")
+        .arg("-o")
+        .arg(operating_dir.join("combined.o"))
+        .args(&object_paths)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "the objects of two modules define the same symbols: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_with_mismatched_header_fails() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    #[cfg(not(windows))]
+    let (serialized_object_path, symbols_object_path) = (
+        operating_dir.join("serialized.o"),
+        operating_dir.join("symbols.o"),
+    );
+    #[cfg(windows)]
+    let (serialized_object_path, symbols_object_path) = (
+        operating_dir.join("serialized.obj"),
+        operating_dir.join("symbols.obj"),
+    );
+
+    for (object_path, format) in [
+        (&serialized_object_path, "serialized"),
+        (&symbols_object_path, "symbols"),
+    ] {
+        WasmerCreateObj {
+            current_dir: operating_dir.clone(),
+            wasm_path: operating_dir.join(create_exe_test_wasm_path()),
+            output_object_path: object_path.clone(),
+            compiler: Compiler::Cranelift,
+            extra_cli_flags: vec!["--object-format", format],
+            ..Default::default()
+        }
+        .run()
+        .context("Failed to create-obj wasm with Wasmer")?;
+    }
+
+    let result = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: serialized_object_path,
+        extra_cli_flags: vec!["--header", "symbols.h"],
+        ..Default::default()
+    }
+    .run();
+    let error = result.expect_err("create-exe accepted a header from another object");
+    assert!(
+        error.to_string().contains("does not match the object file"),
+        "unexpected create-exe error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_list_targets_needs_no_input() -> anyhow::Result<()> {
+    let output = Command::new(get_wasmer_path())
+        .arg("create-exe")
+        .arg("--list-targets")
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "create-exe --list-targets failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout
+            .lines()
+            .any(|line| line.starts_with("x86_64-unknown-linux-gnu")),
+        "x86_64-unknown-linux-gnu is not listed: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_rejects_non_wasm_input() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let input_path = operating_dir.join("not_wasm.txt");
+    fs::write(&input_path, "this is not a wasm module")?;
+
+    let error = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: input_path,
+        ..Default::default()
+    }
+    .run()
+    .expect_err("create-exe accepted a file that is not Wasm");
+    assert!(
+        error.to_string().contains("Wasm magic bytes"),
+        "unexpected create-exe error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_wat_input_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wat_path = operating_dir.join("hello.wat");
+    fs::write(
+        &wat_path,
+        r#"(module
+            (import "wasi_unstable" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 8) "Hello, World\n")
+            (func (export "_start")
+                (i32.store (i32.const 0) (i32.const 8))
+                (i32.store (i32.const 4) (i32.const 13))
+                (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 24)))))"#,
+    )?;
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: wat_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wat with Wasmer")?;
+
+    let result = run_code(&operating_dir, &executable_path, &[])
+        .context("Failed to run generated executable")?;
+    assert_eq!(result.lines().collect::<Vec<&str>>(), vec!["Hello, World"]);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_reads_stdin() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let output = Command::new(get_wasmer_path())
+        .current_dir(&operating_dir)
+        .arg("create-exe")
+        .arg("-")
+        .arg(Compiler::Cranelift.to_flag())
+        .arg("-o")
+        .arg(&executable_path)
+        .stdin(fs::File::open(create_exe_test_wasm_path())?)
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer create-exe failed with: stdout: {}\n\nstderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_links_pie_by_default() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let output = Command::new("file").arg(&executable_path).output()?;
+    let description = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        description.contains("pie executable"),
+        "`{}` is not a PIE: {}",
+        executable_path.display(),
+        description
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_run_executes_output() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let output = Command::new(get_wasmer_path())
+        .current_dir(&operating_dir)
+        .arg("create-exe")
+        .arg(create_exe_test_wasm_path())
+        .arg(Compiler::Cranelift.to_flag())
+        .arg("-o")
+        .arg(&executable_path)
+        .arg("--run")
+        .arg("--")
+        .arg("--eval")
+        .arg("function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));")
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "wasmer create-exe --run failed with: stdout: {}\n\nstderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.lines().any(|line| line == "\"Hello, World\""),
+        "unexpected output: {}",
+        stdout
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_bakes_wasi_defaults() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--arg",
+            "--eval",
+            "--arg",
+            "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    // The baked arguments are used when none are given...
+    let result = run_code(&operating_dir, &executable_path, &[])
+        .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["\"Hello, World\""],);
+
+    // ...and replaced by the ones given on the command line.
+    let result = run_code(
+        &operating_dir,
+        &executable_path,
+        &["--eval".to_string(), "print('overridden');".to_string()],
+    )
+    .context("Failed to run generated executable")?;
+    let result_lines = result.lines().collect::<Vec<&str>>();
+    assert_eq!(result_lines, vec!["overridden"],);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_respects_toolchain_env_vars() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run"],
+        envs: vec![
+            ("CFLAGS", "-DWASMER_GREETING='hello world'"),
+            ("LDFLAGS", "-Wl,--verbose"),
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(
+        stdout.contains("\"-DWASMER_GREETING=hello world\""),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("\"-Wl,--verbose\""), "{}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_timings_prints_phases() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--timings"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    for phase in [
+        "compile `qjs.wasm`",
+        "write object",
+        "compile C glue code",
+        "link",
+        "total",
+    ] {
+        assert!(
+            stdout.lines().any(|line| line.starts_with(phase)),
+            "missing `{}` timing: {}",
+            phase,
+            stdout
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_emit_asm_labels_functions() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+    let asm_path = operating_dir.join("qjs.s");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--object-format", "symbols", "--emit-asm", "qjs.s"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let asm = std::fs::read_to_string(&asm_path)?;
+    assert!(
+        asm.lines()
+            .any(|line| line.contains("wasmer_function_") && line.ends_with('`')),
+        "no annotated function label in the disassembly"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_writes_depfile() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--depfile", "wasm.d"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let depfile = fs::read_to_string(operating_dir.join("wasm.d"))?;
+    let (target, prerequisites) = depfile
+        .split_once(": ")
+        .unwrap_or_else(|| panic!("no rule: {}", depfile));
+    assert_eq!(target, executable_path.display().to_string());
+    for prerequisite in ["qjs.wasm", "wasmer.h", "wasm.h"] {
+        assert!(
+            prerequisites.contains(prerequisite),
+            "missing `{}`: {}",
+            prerequisite,
+            depfile
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_prints_sizes() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--object-format", "serialized", "-v"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let executable_size = fs::metadata(&executable_path)?.len();
+    assert!(
+        stdout.contains(&format!("Executable size: {} bytes", executable_size)),
+        "{}",
+        stdout
+    );
+    for part in [
+        "serialized module `qjs.wasm`",
+        "Wasm object",
+        "C glue object",
+    ] {
+        assert!(
+            stdout
+                .lines()
+                .any(|line| line.trim_start().starts_with(part)),
+            "missing `{}` size: {}",
+            part,
+            stdout
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_dry_run_prints_commands() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(stdout.contains("wasmer_main.c"), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("{:?}", executable_path.display().to_string())),
+        "{}",
+        stdout
+    );
+    assert!(!executable_path.exists());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(windows))]
+fn create_exe_passes_library_paths_before_libraries() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "-L", "vendor", "-l", "foo"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let link_command = stdout
+        .lines()
+        .find(|line| line.contains("\"-lfoo\""))
+        .unwrap_or_else(|| panic!("no link command: {}", stdout));
+    // The relative directory is passed as an absolute one.
+    let library_path = link_command
+        .find("\"-L/")
+        .unwrap_or_else(|| panic!("no library path: {}", link_command));
+    assert!(
+        link_command[library_path..]
+            .split(' ')
+            .next()
+            .unwrap_or_default()
+            .ends_with("/vendor\""),
+        "{}",
+        link_command
+    );
+    assert!(library_path < link_command.find("\"-lfoo\"").unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_links_library_paths_verbatim() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+    fs::write(operating_dir.join("libfoo.a"), b"")?;
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "-l", "libfoo.a", "-l", "bar"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(stdout.contains("\"libfoo.a\""), "{}", stdout);
+    assert!(!stdout.contains("-llibfoo.a"), "{}", stdout);
+    assert!(stdout.contains("\"-lbar\""), "{}", stdout);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(windows))]
+fn create_exe_no_default_libs() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    // The system libraries are only passed to the link, not to the compilation of the C glue.
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: wasm_path.clone(),
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let (compile_commands, link_commands): (Vec<&str>, Vec<&str>) = stdout
+        .lines()
+        .filter(|line| line.contains("\"-o\""))
+        .partition(|line| line.contains("\"-c\""));
+    assert!(!compile_commands.is_empty(), "{}", stdout);
+    for command in compile_commands {
+        assert!(!command.contains("\"-l"), "{}", command);
+        assert!(!command.contains("\"-pthread\""), "{}", command);
+        assert!(!command.contains("\"-v\""), "{}", command);
+    }
+    assert!(
+        link_commands
+            .iter()
+            .any(|command| command.contains("\"-ldl\"")),
+        "{}",
+        stdout
+    );
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "--no-default-libs", "-l", "m"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(!stdout.contains("\"-ldl\""), "{}", stdout);
+    assert!(!stdout.contains("\"-pthread\""), "{}", stdout);
+    assert!(stdout.contains("\"-lm\""), "{}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_passes_extra_cc_and_linker_args() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--dry-run",
+            "--object-format",
+            "serialized",
+            "--cc-arg",
+            "-DFROM_CC_ARG",
+            "--linker-arg",
+            "-Wl,--from-linker-arg",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let compile_command = stdout
+        .lines()
+        .find(|line| line.contains("\"-c\""))
+        .unwrap_or_else(|| panic!("no compile command: {}", stdout));
+    assert!(compile_command.contains("\"-DFROM_CC_ARG\""), "{}", stdout);
+    assert!(!compile_command.contains("--from-linker-arg"), "{}", stdout);
+    let link_command = stdout
+        .lines()
+        .find(|line| line.contains("\"-Wl,--from-linker-arg\""))
+        .unwrap_or_else(|| panic!("no link command: {}", stdout));
+    assert!(!link_command.contains("FROM_CC_ARG"), "{}", stdout);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_gc_sections() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: wasm_path.clone(),
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "--object-format", "serialized"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(stdout.contains("\"-ffunction-sections\""), "{}", stdout);
+    assert!(stdout.contains("\"-fdata-sections\""), "{}", stdout);
+    assert!(stdout.contains("\"-Wl,--gc-sections\""), "{}", stdout);
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir,
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--dry-run",
+            "--object-format",
+            "serialized",
+            "--no-gc-sections",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(!stdout.contains("-ffunction-sections"), "{}", stdout);
+    assert!(!stdout.contains("gc-sections"), "{}", stdout);
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_check_discards_executable() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--check"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to check create-exe wasm with Wasmer")?;
+
+    assert!(!executable_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_emit_object() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let object_path = operating_dir.join("wasm.o");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: object_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--emit-object"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create an object with create-exe")?;
+
+    assert!(object_path.is_file());
+    let header = std::fs::read_to_string(operating_dir.join("wasm.h"))?;
+    assert!(header.contains("wasmer_object_module_new"), "{}", header);
+    assert!(
+        header.contains("#define WASMER_MODULE_ALIGN 16\n"),
+        "{}",
+        header
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_emit_object_without_wasmer_installation() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let object_path = operating_dir.join("wasm.o");
+    // An empty directory, relative to `current_dir`.
+    std::fs::create_dir(operating_dir.join("empty-wasmer-dir"))?;
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: object_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--emit-object"],
+        envs: vec![("WASMER_DIR", "empty-wasmer-dir")],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create an object with create-exe")?;
+
+    assert!(object_path.is_file());
+    assert!(operating_dir.join("wasm.h").is_file());
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_reports_missing_c_compiler() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+
+    let error = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--cc", "wasmer-missing-c-compiler"],
+        ..Default::default()
+    }
+    .run()
+    .expect_err("create-exe ran without a C compiler");
+    assert!(
+        error.to_string().contains("Install a C toolchain"),
+        "unexpected create-exe error: {}",
+        error
+    );
+
+    Ok(())
+}
+
+#[test]
+fn create_exe_allow_restricts_wasi() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    #[cfg(not(windows))]
+    let executable_path = operating_dir.join("wasm.out");
+    #[cfg(windows)]
+    let executable_path = operating_dir.join("wasm.exe");
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--dry-run",
+            "--object-format",
+            "serialized",
+            "--keep-intermediate",
+            "build",
+            "--allow",
+            "fs,clock",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let main_c_source = fs::read_to_string(operating_dir.join("build").join("wasmer_main.c"))?;
+    assert!(
+        main_c_source.contains("#define WASMER_RESTRICT_WASI"),
+        "{}",
+        main_c_source
+    );
+    assert!(
+        main_c_source.contains(
+            "wasmer_allowed_wasi_imports[] = {\"proc_exit\", \"proc_raise\", \"sched_yield\", \"fd_\", \"path_\", \"clock_\", NULL};"
+        ),
+        "{}",
+        main_c_source
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_writes_linker_map() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: executable_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "--map", "wasm.map"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let link_command = stdout
+        .lines()
+        .find(|line| line.contains("-Wl,-Map,"))
+        .unwrap_or_else(|| panic!("no link command: {}", stdout));
+    assert!(link_command.contains("/wasm.map\""), "{}", link_command);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_shared_library() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let library_path = operating_dir.join("libwasm");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path,
+        native_executable_path: library_path,
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec![
+            "--dry-run",
+            "--object-format",
+            "serialized",
+            "--keep-intermediate",
+            "build",
+            "--output-kind",
+            "shared-lib",
+        ],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create a shared library with create-exe")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    let compile_command = stdout
+        .lines()
+        .find(|line| line.contains("\"-c\""))
+        .unwrap_or_else(|| panic!("no compile command: {}", stdout));
+    assert!(compile_command.contains("\"-fPIC\""), "{}", stdout);
+    let link_command = stdout
+        .lines()
+        .find(|line| line.contains("\"-shared\""))
+        .unwrap_or_else(|| panic!("no link command: {}", stdout));
+    assert!(!link_command.contains("\"-pie\""), "{}", link_command);
+    assert!(link_command.contains("libwasm.so\""), "{}", link_command);
+
+    let glue = fs::read_to_string(operating_dir.join("build").join("wasmer_main.c"))?;
+    assert!(glue.contains("wasmer_module_instantiate"), "{}", glue);
+    assert!(!glue.contains("int main("), "{}", glue);
+
+    WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: operating_dir.join(create_exe_test_wasm_path()),
+        native_executable_path: operating_dir.join("libwasm.so"),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--output-kind", "shared-lib"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create a shared library with create-exe")?;
+
+    let output = Command::new("nm")
+        .arg("-D")
+        .arg("--defined-only")
+        .arg(operating_dir.join("libwasm.so"))
+        .output()?;
+    let symbols = String::from_utf8_lossy(&output.stdout);
+    for symbol in [
+        "wasmer_module_new",
+        "wasmer_module_instantiate",
+        "wasm_engine_new",
+    ] {
+        assert!(
+            symbols
+                .lines()
+                .any(|line| line.ends_with(&format!(" {}", symbol))),
+            "`{}` isn't exported: {}",
+            symbol,
+            symbols
+        );
+    }
+
+    // A program creating the embedded module with the libwasmer the library embeds.
+    fs::write(
+        operating_dir.join("main.c"),
+        r#"
+#include <stdio.h>
+#include "wasm.h"
+
+extern wasm_module_t *wasmer_module_new(wasm_store_t *store, const char *module_name);
+
+int main() {
+  wasm_engine_t *engine = wasm_engine_new();
+  wasm_store_t *store = wasm_store_new(engine);
+  wasm_module_t *module = wasmer_module_new(store, "module");
+  if (!module) {
+    printf("could not create the module\n");
+    return 1;
+  }
+  printf("created the module\n");
+  wasm_module_delete(module);
+  wasm_store_delete(store);
+  wasm_engine_delete(engine);
+  return 0;
+}
+"#,
+    )?;
+    let output = Command::new("cc")
+        .current_dir(&operating_dir)
+        .arg("main.c")
+        .arg(format!("-I{}", WASMER_INCLUDE_PATH))
+        .arg(format!("-L{}", operating_dir.display()))
+        .arg("-lwasm")
+        .arg(format!("-Wl,-rpath,{}", operating_dir.display()))
+        .arg("-o")
+        .arg("main")
+        .output()?;
+    assert!(
+        output.status.success(),
+        "could not build a program using the shared library: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let result = run_code(&operating_dir, &operating_dir.join("main"), &[])
+        .context("Failed to run the program using the shared library")?;
+    assert_eq!(result.trim(), "created the module");
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_reproducible() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let first_path = operating_dir.join("first.out");
+    let second_path = operating_dir.join("second.out");
+
+    for executable_path in [&first_path, &second_path] {
+        WasmerCreateExe {
+            current_dir: operating_dir.clone(),
+            wasm_path: wasm_path.clone(),
+            native_executable_path: executable_path.clone(),
+            compiler: Compiler::Cranelift,
+            extra_cli_flags: vec!["--reproducible", "--no-cache"],
+            ..Default::default()
+        }
+        .run()
+        .context("Failed to create-exe wasm with Wasmer")?;
+    }
+
+    assert!(fs::read(&first_path)? == fs::read(&second_path)?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn create_exe_strict_c() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let operating_dir: PathBuf = temp_dir.path().to_owned();
+
+    let wasm_path = operating_dir.join(create_exe_test_wasm_path());
+    let executable_path = operating_dir.join("wasm.out");
+
+    let stdout = WasmerCreateExe {
+        current_dir: operating_dir.clone(),
+        wasm_path: wasm_path.clone(),
+        native_executable_path: executable_path.clone(),
+        compiler: Compiler::Cranelift,
+        extra_cli_flags: vec!["--dry-run", "--object-format", "serialized"],
+        ..Default::default()
+    }
+    .run()
+    .context("Failed to create-exe wasm with Wasmer")?;
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    assert!(!stdout.contains("-Werror"), "{}", stdout);
+
+    // The generated glue code must compile without warnings.
+    for object_format in ["serialized", "symbols"] {
+        WasmerCreateExe {
+            current_dir: operating_dir.clone(),
+            wasm_path: wasm_path.clone(),
+            native_executable_path: executable_path.clone(),
+            compiler: Compiler::Cranelift,
+            extra_cli_flags: vec!["--strict-c", "--object-format", object_format],
+            ..Default::default()
+        }
+        .run()
+        .context("Failed to create-exe wasm with Wasmer")?;
+
+        let result = run_code(
+            &operating_dir,
+            &executable_path,
+            &["--eval".to_string(), "function greet(name) { return JSON.stringify('Hello, ' + name); }; print(greet('World'));".to_string()],
+        )
+        .context("Failed to run generated executable")?;
+        let result_lines = result.lines().collect::<Vec<&str>>();
+        assert_eq!(result_lines, vec!["\"Hello, World\""],);
+    }
+
+    Ok(())
+}